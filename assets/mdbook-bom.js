@@ -0,0 +1,313 @@
+/*
+ * Behavior for the tables inserted by mdbook-bom: the Show All/Hide All
+ * buttons, a page-wide expand control, open state kept per page, image zoom,
+ * opening tables for `{{#bom-part}}` links, the gathered checklist and the
+ * build quantity calculator.
+ *
+ * This file is written by `mdbook-bom install` and refreshed on every build.
+ * It is loaded as an additional-js file and the pages contain no inline
+ * scripts or event handlers, so it works under a strict Content-Security-Policy.
+ */
+(function () {
+    "use strict";
+
+    var STORAGE_KEY = "mdbook-bom:open:" + location.pathname;
+    // Shared by the pages of one book, so the number carries over from chapter to
+    // chapter but not to other books served from the same site
+    var BUILD_QUANTITY_KEY = "mdbook-bom:build-quantity:" + bookRoot();
+
+    // mdBook defines `path_to_root` on every page; it is empty on top-level pages
+    function bookRoot() {
+        var root = typeof path_to_root === "string" ? path_to_root : "";
+        return new URL(root || "./", location.href).pathname;
+    }
+
+    // All tables of the page, or those of one step or overview
+    function tables(section) {
+        var selector = "details.bom-table";
+        if (section !== null && section !== undefined) {
+            selector += '[data-bom-section="' + CSS.escape(section) + '"]';
+        }
+        return Array.prototype.slice.call(document.querySelectorAll(selector));
+    }
+
+    function allOpen(group) {
+        return group.length > 0 && group.every(function (table) { return table.open; });
+    }
+
+    function loadIds(key) {
+        try {
+            return JSON.parse(localStorage.getItem(key)) || [];
+        } catch (e) {
+            return [];
+        }
+    }
+
+    function saveIds(key, ids) {
+        try {
+            if (ids.length > 0) {
+                localStorage.setItem(key, JSON.stringify(ids));
+            } else {
+                localStorage.removeItem(key);
+            }
+        } catch (e) {
+            // Storage is unavailable, e.g. disabled by the browser
+        }
+    }
+
+    function saveState() {
+        saveIds(STORAGE_KEY, tables()
+            .filter(function (table) { return table.open && table.id; })
+            .map(function (table) { return table.id; }));
+    }
+
+    // Checklist boxes of all tables, or of one step
+    function checkboxes(section) {
+        return tables(section).reduce(function (all, table) {
+            return all.concat(Array.prototype.slice.call(table.querySelectorAll("input.bom-check")));
+        }, []);
+    }
+
+    // Ticks are saved per page and book version, so a new version starts empty
+    function checklistKey() {
+        var progress = document.querySelector(".bom-progress[data-bom-checklist]");
+        if (!progress) {
+            return null;
+        }
+        return "mdbook-bom:checklist:" + progress.getAttribute("data-bom-checklist") + ":" + location.pathname;
+    }
+
+    // A tick is saved as its table's id and the item name, which are unique
+    // together, unlike row ids
+    function checklistItem(box) {
+        return [box.closest("details.bom-table").id, box.getAttribute("data-bom-item")];
+    }
+
+    function saveChecklist() {
+        var key = checklistKey();
+        if (key) {
+            saveIds(key, checkboxes()
+                .filter(function (box) { return box.checked; })
+                .map(checklistItem));
+        }
+    }
+
+    function loadChecklist(key) {
+        var ticked = loadIds(key).filter(Array.isArray);
+        checkboxes().forEach(function (box) {
+            var item = checklistItem(box);
+            if (ticked.some(function (tick) { return tick[0] === item[0] && tick[1] === item[1]; })) {
+                box.checked = true;
+            }
+        });
+    }
+
+    function updateProgress() {
+        document.querySelectorAll(".bom-progress").forEach(function (progress) {
+            var boxes = checkboxes(progress.getAttribute("data-bom-section"));
+            var gathered = boxes.filter(function (box) { return box.checked; }).length;
+            progress.textContent = gathered + " / " + boxes.length + " gathered";
+            progress.classList.toggle("bom-progress-done", boxes.length > 0 && gathered === boxes.length);
+        });
+    }
+
+    function loadBuildQuantity() {
+        try {
+            var units = parseInt(localStorage.getItem(BUILD_QUANTITY_KEY), 10);
+            return units >= 1 ? units : 1;
+        } catch (e) {
+            return 1;
+        }
+    }
+
+    function saveBuildQuantity(units) {
+        try {
+            if (units > 1) {
+                localStorage.setItem(BUILD_QUANTITY_KEY, String(units));
+            } else {
+                localStorage.removeItem(BUILD_QUANTITY_KEY);
+            }
+        } catch (e) {
+            // Storage is unavailable, e.g. disabled by the browser
+        }
+    }
+
+    // Quantity cells hold the amount for one unit in `data-bom-quantity`
+    function applyBuildQuantity(units) {
+        document.querySelectorAll(".bom-quantity").forEach(function (cell) {
+            var perUnit = parseInt(cell.getAttribute("data-bom-quantity"), 10);
+            cell.textContent = String(perUnit * units);
+            cell.classList.toggle("bom-quantity-scaled", units > 1);
+            if (units > 1) {
+                cell.title = perUnit + " per unit × " + units + " units";
+            } else {
+                cell.removeAttribute("title");
+            }
+        });
+        document.querySelectorAll("input.bom-build-quantity-input").forEach(function (input) {
+            if (input !== document.activeElement) {
+                input.value = String(units);
+            }
+        });
+    }
+
+    // Empty or invalid input while typing leaves the quantities as they are
+    function parseBuildQuantity(input) {
+        var units = Number(input.value);
+        return Number.isInteger(units) && units >= 1 ? units : null;
+    }
+
+    // Buttons without a section are the page-wide control
+    function updateButtons() {
+        document.querySelectorAll(".bom-show-all-button").forEach(function (button) {
+            var section = button.getAttribute("data-bom-section");
+            var open = allOpen(tables(section));
+            button.setAttribute("aria-expanded", open ? "true" : "false");
+            if (section === null) {
+                button.textContent = open ? "Collapse all tables" : "Expand all tables";
+            } else {
+                button.textContent = open ? "Hide All" : "Show All";
+            }
+        });
+    }
+
+    function toggleGroup(section) {
+        var group = tables(section);
+        var open = !allOpen(group);
+        group.forEach(function (table) { table.open = open; });
+    }
+
+    // Opens the table holding a linked row, e.g. from a `{{#bom-part}}` reference
+    function revealTarget(hash) {
+        var id = decodeURIComponent((hash || "").replace(/^#/, ""));
+        var target = id && document.getElementById(id);
+        var details = target && target.closest("details.bom-table");
+        if (details && !details.open) {
+            details.open = true;
+            target.scrollIntoView();
+        }
+    }
+
+    function toggleThumbnail(image) {
+        var large = image.classList.toggle("bom-thumbnail-large");
+        image.setAttribute("aria-pressed", large ? "true" : "false");
+    }
+
+    function isEditable(element) {
+        return element.isContentEditable || /^(INPUT|TEXTAREA|SELECT)$/.test(element.tagName);
+    }
+
+    function init() {
+        if (tables().length === 0) {
+            return;
+        }
+
+        loadIds(STORAGE_KEY).forEach(function (id) {
+            var table = document.getElementById(id);
+            if (table && table.matches("details.bom-table")) {
+                table.open = true;
+            }
+        });
+        revealTarget(location.hash);
+
+        var main = document.querySelector("main");
+        if (main) {
+            var pageButton = document.createElement("button");
+            pageButton.type = "button";
+            pageButton.className = "bom-show-all-button bom-page-toggle";
+            pageButton.title = "Expand or collapse every table on this page (E)";
+            main.insertBefore(pageButton, main.firstChild);
+        }
+
+        document.querySelectorAll("img.bom-thumbnail").forEach(function (image) {
+            image.tabIndex = 0;
+            image.setAttribute("role", "button");
+            image.setAttribute("aria-pressed", "false");
+        });
+        var key = checklistKey();
+        if (key) {
+            loadChecklist(key);
+        }
+        updateButtons();
+        updateProgress();
+        applyBuildQuantity(loadBuildQuantity());
+
+        // `toggle` does not bubble, so it is caught on the way down
+        document.addEventListener("toggle", function (event) {
+            if (event.target.matches && event.target.matches("details.bom-table")) {
+                saveState();
+                updateButtons();
+            }
+        }, true);
+
+        document.addEventListener("click", function (event) {
+            var button = event.target.closest(".bom-show-all-button");
+            if (button) {
+                toggleGroup(button.getAttribute("data-bom-section"));
+                return;
+            }
+            var clear = event.target.closest(".bom-checklist-clear");
+            if (clear) {
+                checkboxes(clear.getAttribute("data-bom-section")).forEach(function (box) { box.checked = false; });
+                saveChecklist();
+                updateProgress();
+                return;
+            }
+            var image = event.target.closest("img.bom-thumbnail");
+            if (image) {
+                toggleThumbnail(image);
+                return;
+            }
+            var link = event.target.closest("a.bom-part");
+            if (link) {
+                revealTarget(link.hash);
+            }
+        });
+
+        document.addEventListener("change", function (event) {
+            if (event.target.matches("input.bom-check")) {
+                saveChecklist();
+                updateProgress();
+            } else if (event.target.matches("input.bom-build-quantity-input")) {
+                // Restore the last valid number once the field is left
+                var units = parseBuildQuantity(event.target) || loadBuildQuantity();
+                event.target.value = String(units);
+                saveBuildQuantity(units);
+                applyBuildQuantity(units);
+            }
+        });
+
+        document.addEventListener("input", function (event) {
+            if (event.target.matches("input.bom-build-quantity-input")) {
+                var units = parseBuildQuantity(event.target);
+                if (units !== null) {
+                    saveBuildQuantity(units);
+                    applyBuildQuantity(units);
+                }
+            }
+        });
+
+        document.addEventListener("keydown", function (event) {
+            if (event.ctrlKey || event.metaKey || event.altKey || isEditable(event.target)) {
+                return;
+            }
+            if ((event.key === "Enter" || event.key === " ") && event.target.matches("img.bom-thumbnail")) {
+                event.preventDefault();
+                toggleThumbnail(event.target);
+            } else if (event.key === "e" || event.key === "E") {
+                event.preventDefault();
+                toggleGroup(null);
+            }
+        });
+
+        window.addEventListener("hashchange", function () {
+            revealTarget(location.hash);
+        });
+    }
+
+    if (document.readyState === "loading") {
+        document.addEventListener("DOMContentLoaded", init);
+    } else {
+        init();
+    }
+})();
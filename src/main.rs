@@ -1,12 +1,19 @@
 use calamine::{open_workbook, RangeDeserializerBuilder, Reader, Xlsx};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use indexmap::IndexMap;
 use log::warn;
-use clap::{Arg, ArgMatches, Command};
-use mdbook::book::{Book, BookItem};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use mdbook::book::{Book, BookItem, Chapter};
 use mdbook::errors::Error;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext};
-use rust_xlsxwriter::Workbook;
+use mdbook::MDBook;
+use rust_xlsxwriter::{
+    DocProperties, ExcelDateTime, Format, FormatAlign, FormatBorder, Workbook, Worksheet,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io;
 
 pub fn make_app() -> Command {
@@ -17,17 +24,129 @@ pub fn make_app() -> Command {
                 .arg(Arg::new("renderer").required(true))
                 .about("Check whether a renderer is supported by this preprocessor"),
         )
+        .subcommand(
+            Command::new("generate")
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Root directory of the book (containing book.toml)"),
+                )
+                .arg(
+                    Arg::new("build-quantity")
+                        .long("build-quantity")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("Number of units to check stock for (overrides build-quantity in book.toml)"),
+                )
+                .about("Generate the BOM outputs for a book without running mdbook"),
+        )
+        .subcommand(
+            Command::new("check")
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Root directory of the book (containing book.toml)"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["text", "json", "github"])
+                        .default_value("text")
+                        .help("Output format for diagnostics"),
+                )
+                .about("Validate front matter against the inventory and step headers without writing anything"),
+        )
+        .subcommand(
+            Command::new("schema")
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Root directory of the book (containing book.toml)"),
+                )
+                .arg(
+                    Arg::new("inventory")
+                        .long("inventory")
+                        .action(ArgAction::SetTrue)
+                        .help("Restrict part names to those in the inventory (BOM_INVENTORY_FILE)"),
+                )
+                .about("Print a JSON Schema for the chapter front matter"),
+        )
+        .subcommand(
+            Command::new("lsp")
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Root directory of the book (default: the editor's workspace root)"),
+                )
+                .about("Run a language server for front matter editing over stdio"),
+        )
+        .subcommand(
+            Command::new("install")
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Root directory of the book (containing book.toml)"),
+                )
+                .about("Write the table stylesheet and script and add them to output.html in book.toml"),
+        )
 }
 
 fn main() {
+    let matches = make_app().get_matches();
+
+    // The book's own .env takes precedence when the book is given explicitly
+    if let Some((_, sub_args)) = matches.subcommand() {
+        if let Ok(Some(book_dir)) = sub_args.try_get_one::<String>("book") {
+            let _ = dotenvy::from_path(std::path::Path::new(book_dir).join(".env"));
+        }
+    }
+
     // Load .env file if present (for local configuration)
     // Silently ignore if .env file doesn't exist
     let _ = dotenvy::dotenv();
 
-    let matches = make_app().get_matches();
-
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("generate") {
+        if let Err(e) = handle_generate(sub_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("schema") {
+        if let Err(e) = handle_schema(sub_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("lsp") {
+        if let Err(e) = handle_lsp(sub_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("install") {
+        if let Err(e) = handle_install(sub_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("check") {
+        match handle_check(sub_args) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     } else if let Err(e) = handle_preprocessing() {
         eprintln!("{}", e);
         std::process::exit(1);
@@ -35,16 +154,241 @@ fn main() {
 }
 
 fn handle_supports(sub_args: &ArgMatches) -> ! {
-    let renderer = sub_args
+    let _renderer = sub_args
         .get_one::<String>("renderer")
         .expect("Required argument");
-    let supported = renderer != "not-supported";
 
-    if supported {
-        std::process::exit(0);
+    // Every renderer is supported: the front matter always has to be stripped,
+    // and the output mode is picked per renderer in `BomConfig::render_mode`
+    std::process::exit(0);
+}
+
+fn handle_generate(sub_args: &ArgMatches) -> Result<(), Error> {
+    let book_dir = sub_args.get_one::<String>("book").expect("Has default");
+
+    // Loads book.toml and SUMMARY.md without running any preprocessors or renderers
+    let mut md = MDBook::load(book_dir)?;
+    let mut config = BomConfig::from_book_config(&md.config)?;
+    if let Some(build_quantity) = sub_args.get_one::<u32>("build-quantity") {
+        config.build_quantity = *build_quantity;
+    }
+    let written = build_bom(&md.root, &mut md.book, &config, None)?;
+
+    for path in written {
+        println!("Wrote {}", path);
+    }
+    Ok(())
+}
+
+// Returns whether the book passed without errors
+fn handle_check(sub_args: &ArgMatches) -> Result<bool, Error> {
+    let book_dir = sub_args.get_one::<String>("book").expect("Has default");
+    let format = sub_args.get_one::<String>("format").expect("Has default");
+
+    let md = MDBook::load(book_dir)?;
+    let matcher = BomConfig::from_book_config(&md.config)?.step_matcher()?;
+    let inventory = load_inventory_from_env(&md.root)?;
+    let src_dir = std::path::Path::new(book_dir).join(&md.config.book.src);
+
+    let mut diagnostics = Vec::new();
+    for item in md.book.iter() {
+        if let BookItem::Chapter(ch) = item {
+            if let Some(path) = &ch.path {
+                let file = src_dir.join(path).to_string_lossy().to_string();
+                diagnostics.extend(check_chapter(&ch.content, &file, &inventory, &matcher));
+            }
+        }
+    }
+
+    print_diagnostics(&diagnostics, format)?;
+    Ok(!diagnostics.iter().any(|d| d.severity == Severity::Error))
+}
+
+fn handle_schema(sub_args: &ArgMatches) -> Result<(), Error> {
+    let inventory = if sub_args.get_flag("inventory") {
+        let book_dir = sub_args.get_one::<String>("book").expect("Has default");
+        Some(load_inventory_from_env(std::path::Path::new(book_dir))?)
     } else {
-        std::process::exit(1);
+        None
+    };
+
+    let schema = generate_front_matter_schema(inventory.as_ref())?;
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+fn handle_lsp(sub_args: &ArgMatches) -> Result<(), Error> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = lsp_types::ServerCapabilities {
+        text_document_sync: Some(lsp_types::TextDocumentSyncCapability::Kind(
+            lsp_types::TextDocumentSyncKind::FULL,
+        )),
+        completion_provider: Some(lsp_types::CompletionOptions {
+            trigger_characters: Some(vec!["\"".to_string(), " ".to_string()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(lsp_types::HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    let init_params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let init_params: lsp_types::InitializeParams = serde_json::from_value(init_params)?;
+
+    // An explicit --book wins over the editor's workspace root
+    #[allow(deprecated)]
+    let workspace_root = init_params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| &folder.uri)
+        .or(init_params.root_uri.as_ref())
+        .and_then(|uri| uri.to_file_path().ok());
+    let book_dir = sub_args.get_one::<String>("book").expect("Has default");
+    let book_root = match (sub_args.value_source("book"), workspace_root) {
+        (Some(ValueSource::CommandLine), _) | (_, None) => std::path::PathBuf::from(book_dir),
+        (_, Some(root)) => root,
+    };
+    let _ = dotenvy::from_path(book_root.join(".env"));
+
+    // Step header patterns come from book.toml; fall back to the defaults without one
+    let book_toml = book_root.join("book.toml");
+    let step_matcher = if book_toml.exists() {
+        mdbook::Config::from_disk(&book_toml)
+            .and_then(|config| BomConfig::from_book_config(&config))
+            .and_then(|config| config.step_matcher())
+    } else {
+        Ok(StepMatcher::default())
+    };
+
+    let mut server = LanguageServer {
+        connection: &connection,
+        inventory: InventoryCache {
+            path: std::env::var("BOM_INVENTORY_FILE")
+                .ok()
+                .map(|path| resolve_book_relative_path(&path, &book_root)),
+            modified: None,
+            attempted: false,
+            inventory: None,
+        },
+        documents: HashMap::new(),
+        step_matcher: StepMatcher::default(),
+    };
+    match step_matcher {
+        Ok(matcher) => server.step_matcher = matcher,
+        Err(e) => server.show_message(
+            lsp_types::MessageType::WARNING,
+            format!("mdbook-bom: {}; using the default step header pattern", e),
+        )?,
+    }
+    if server.inventory.path.is_none() {
+        server.show_message(
+            lsp_types::MessageType::WARNING,
+            "mdbook-bom: BOM_INVENTORY_FILE is not set; completions and diagnostics are disabled"
+                .to_string(),
+        )?;
+    }
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                let response = server.handle_request(req)?;
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => server.handle_notification(not)?,
+            Message::Response(_) => {}
+        }
+    }
+
+    drop(server);
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn handle_install(sub_args: &ArgMatches) -> Result<(), Error> {
+    let book_dir = sub_args.get_one::<String>("book").expect("Has default");
+    let book_root = std::path::Path::new(book_dir);
+    let book_toml = book_root.join("book.toml");
+
+    // Edited as a document so comments and formatting in book.toml survive
+    let text = std::fs::read_to_string(&book_toml)
+        .map_err(|e| Error::msg(format!("Failed to read {}: {}", book_toml.display(), e)))?;
+    let config = BomConfig::from_book_config(&text.parse()?)?;
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|e| Error::msg(format!("Failed to parse {}: {}", book_toml.display(), e)))?;
+    let output = doc
+        .entry("output")
+        .or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            toml_edit::Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| Error::msg("`output` in book.toml is not a table"))?;
+    // mdbook only renders HTML by default when no renderer is configured, so books
+    // with other renderers are left as they are
+    if !output.contains_key("html") && !output.is_empty() {
+        let renderers: Vec<&str> = output.iter().map(|(name, _)| name).collect();
+        println!(
+            "{} configures no HTML output, only {}; the stylesheet and script only apply to HTML, so nothing was installed",
+            book_toml.display(),
+            renderers.join(", ")
+        );
+        return Ok(());
+    }
+    let html = output
+        .entry("html")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| Error::msg("`output.html` in book.toml is not a table"))?;
+    let mut changed = false;
+    for (key, file) in [
+        ("additional-css", STYLESHEET_FILE),
+        ("additional-js", SCRIPT_FILE),
+    ] {
+        let paths = html
+            .entry(key)
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "`output.html.{}` in book.toml is not an array",
+                    key
+                ))
+            })?;
+        if !paths
+            .iter()
+            .any(|path| path.as_str().map(|p| p.trim_start_matches("./")) == Some(file))
+        {
+            paths.push(file);
+            changed = true;
+            println!(
+                "Added {} to output.html.{} in {}",
+                file,
+                key,
+                book_toml.display()
+            );
+        }
     }
+    if changed {
+        std::fs::write(&book_toml, doc.to_string())
+            .map_err(|e| Error::msg(format!("Failed to write {}: {}", book_toml.display(), e)))?;
+    }
+
+    for (file, content) in [
+        (STYLESHEET_FILE, stylesheet(&config)),
+        (SCRIPT_FILE, SCRIPT.to_string()),
+    ] {
+        let path = book_root.join(file);
+        if write_asset(&path, &content)? {
+            println!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
 }
 
 fn handle_preprocessing() -> Result<(), Error> {
@@ -54,6 +398,19 @@ fn handle_preprocessing() -> Result<(), Error> {
     Ok(())
 }
 
+// Front matter keys of all component categories
+const BOM_CATEGORIES: [&str; 8] = [
+    "hardware",
+    "electronics",
+    "custom_parts",
+    "consumables",
+    "tools",
+    "assemblies",
+    "subassemblies",
+    "units",
+];
+
+#[derive(Default)]
 struct Inventory {
     fasteners: HashMap<String, InventoryFastener>,
     electronics: HashMap<String, InventoryElectronic>,
@@ -68,18 +425,7 @@ struct Inventory {
 impl Inventory {
     fn load(excel_path: &str) -> Result<Self, Error> {
         // Expand home directory if needed
-        let expanded_path = if let Some(stripped) = excel_path.strip_prefix("~/") {
-            if let Some(home) = std::env::var_os("HOME") {
-                let home_path = std::path::Path::new(&home);
-                home_path.join(stripped).to_string_lossy().to_string()
-            } else {
-                return Err(Error::msg(
-                    "Cannot expand ~ - HOME environment variable not set",
-                ));
-            }
-        } else {
-            excel_path.to_string()
-        };
+        let expanded_path = expand_home_path(excel_path)?;
 
         // Check if file exists first
         if !std::path::Path::new(&expanded_path).exists() {
@@ -110,6 +456,135 @@ impl Inventory {
         })
     }
 
+    // Category names match the front matter keys
+    fn contains(&self, category: &str, name: &str) -> bool {
+        match category {
+            "hardware" => self.fasteners.contains_key(name),
+            "electronics" => self.electronics.contains_key(name),
+            "custom_parts" => self.custom_parts.contains_key(name),
+            "consumables" => self.consumables.contains_key(name),
+            "tools" => self.tools.contains_key(name),
+            "assemblies" => self.assemblies.contains_key(name),
+            "subassemblies" => self.subassemblies.contains_key(name),
+            "units" => self.units.contains_key(name),
+            _ => false,
+        }
+    }
+
+    fn names(&self, category: &str) -> Vec<&String> {
+        let mut names: Vec<&String> = match category {
+            "hardware" => self.fasteners.keys().collect(),
+            "electronics" => self.electronics.keys().collect(),
+            "custom_parts" => self.custom_parts.keys().collect(),
+            "consumables" => self.consumables.keys().collect(),
+            "tools" => self.tools.keys().collect(),
+            "assemblies" => self.assemblies.keys().collect(),
+            "subassemblies" => self.subassemblies.keys().collect(),
+            "units" => self.units.keys().collect(),
+            _ => Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    // One-line summary of an item: its description, or its brand for tools
+    fn describe(&self, category: &str, name: &str) -> Option<String> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.description.clone(),
+            "electronics" => self.electronics.get(name)?.description.clone(),
+            "custom_parts" => self.custom_parts.get(name)?.description.clone(),
+            "consumables" => self.consumables.get(name)?.description.clone(),
+            "tools" => self
+                .tools
+                .get(name)?
+                .brand
+                .as_ref()
+                .map(|brand| format!("Brand: {}", brand)),
+            "assemblies" => self.assemblies.get(name)?.description.clone(),
+            "subassemblies" => self.subassemblies.get(name)?.description.clone(),
+            "units" => self.units.get(name)?.description.clone(),
+            _ => None,
+        }
+    }
+
+    fn image(&self, category: &str, name: &str) -> Option<&str> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.image.as_deref(),
+            "electronics" => self.electronics.get(name)?.image.as_deref(),
+            "custom_parts" => self.custom_parts.get(name)?.image.as_deref(),
+            "consumables" => self.consumables.get(name)?.image.as_deref(),
+            "tools" => self.tools.get(name)?.image.as_deref(),
+            "assemblies" => self.assemblies.get(name)?.image.as_deref(),
+            "subassemblies" => self.subassemblies.get(name)?.image.as_deref(),
+            "units" => self.units.get(name)?.image.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn datasheet(&self, category: &str, name: &str) -> Option<&str> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.datasheet.as_deref(),
+            "electronics" => self.electronics.get(name)?.datasheet.as_deref(),
+            "custom_parts" => self.custom_parts.get(name)?.datasheet.as_deref(),
+            "consumables" => self.consumables.get(name)?.datasheet.as_deref(),
+            "tools" => self.tools.get(name)?.datasheet.as_deref(),
+            "assemblies" => self.assemblies.get(name)?.datasheet.as_deref(),
+            "subassemblies" => self.subassemblies.get(name)?.datasheet.as_deref(),
+            "units" => self.units.get(name)?.datasheet.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn supplier_url(&self, category: &str, name: &str) -> Option<&str> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.url.as_deref(),
+            "electronics" => self.electronics.get(name)?.url.as_deref(),
+            "custom_parts" => self.custom_parts.get(name)?.url.as_deref(),
+            "consumables" => self.consumables.get(name)?.url.as_deref(),
+            "tools" => self.tools.get(name)?.url.as_deref(),
+            "assemblies" => self.assemblies.get(name)?.url.as_deref(),
+            "subassemblies" => self.subassemblies.get(name)?.url.as_deref(),
+            "units" => self.units.get(name)?.url.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn on_hand(&self, category: &str, name: &str) -> Option<f64> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.on_hand,
+            "electronics" => self.electronics.get(name)?.on_hand,
+            "custom_parts" => self.custom_parts.get(name)?.on_hand,
+            "consumables" => self.consumables.get(name)?.on_hand,
+            "tools" => self.tools.get(name)?.on_hand,
+            "assemblies" => self.assemblies.get(name)?.on_hand,
+            "subassemblies" => self.subassemblies.get(name)?.on_hand,
+            "units" => self.units.get(name)?.on_hand,
+            _ => None,
+        }
+    }
+
+    fn bin(&self, category: &str, name: &str) -> Option<&str> {
+        match category {
+            "hardware" => self.fasteners.get(name)?.bin.as_deref(),
+            "electronics" => self.electronics.get(name)?.bin.as_deref(),
+            "custom_parts" => self.custom_parts.get(name)?.bin.as_deref(),
+            "consumables" => self.consumables.get(name)?.bin.as_deref(),
+            "tools" => self.tools.get(name)?.bin.as_deref(),
+            "assemblies" => self.assemblies.get(name)?.bin.as_deref(),
+            "subassemblies" => self.subassemblies.get(name)?.bin.as_deref(),
+            "units" => self.units.get(name)?.bin.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn categories_of(&self, name: &str) -> Vec<&'static str> {
+        BOM_CATEGORIES
+            .iter()
+            .copied()
+            .filter(|category| self.contains(category, name))
+            .collect()
+    }
+
     fn load_fasteners_from_excel(
         excel_path: &str,
     ) -> Result<HashMap<String, InventoryFastener>, Error> {
@@ -333,6 +808,21 @@ impl Inventory {
     }
 }
 
+fn expand_home_path(path: &str) -> Result<String, Error> {
+    if let Some(stripped) = path.strip_prefix("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            let home_path = std::path::Path::new(&home);
+            Ok(home_path.join(stripped).to_string_lossy().to_string())
+        } else {
+            Err(Error::msg(
+                "Cannot expand ~ - HOME environment variable not set",
+            ))
+        }
+    } else {
+        Ok(path.to_string())
+    }
+}
+
 pub struct BomPreprocessor;
 
 impl Preprocessor for BomPreprocessor {
@@ -341,357 +831,1294 @@ impl Preprocessor for BomPreprocessor {
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
-        // Read configuration from environment variables (loaded from .env file)
-        let excel_path = std::env::var("BOM_INVENTORY_FILE")
-            .map_err(|_| Error::msg("BOM_INVENTORY_FILE environment variable is required. Set it in .env file in the book directory."))?;
-
-        let raw_output_path = std::env::var("BOM_OUTPUT_PATH")
-            .map_err(|_| Error::msg("BOM_OUTPUT_PATH environment variable is required. Set it in .env file in the book directory."))?;
-
-        let output_path = resolve_output_path(&raw_output_path, &ctx.root)?;
-
-        // Load inventory data
-        let inventory = Inventory::load(&excel_path)?;
-
-        let mut all_fasteners: HashMap<String, BomFastenerItem> = HashMap::new();
-        let mut all_electronics: HashMap<String, BomElectronicItem> = HashMap::new();
-        let mut all_custom_parts: HashMap<String, BomCustomPartItem> = HashMap::new();
-        let mut all_consumables: HashMap<String, BomConsumableItem> = HashMap::new();
-        let mut all_tools: HashMap<String, BomToolItem> = HashMap::new();
-        let mut all_assemblies: HashMap<String, BomAssemblyItem> = HashMap::new();
-        let mut all_subassemblies: HashMap<String, BomSubassemblyItem> = HashMap::new();
-        let mut all_units: HashMap<String, BomUnitItem> = HashMap::new();
-
-        book.for_each_mut(|item: &mut BookItem| {
-            if let BookItem::Chapter(ch) = item {
-                if let Some(front_matter) = extract_front_matter(&ch.content) {
-                    // Remove front matter from content
-                    let content_without_fm = remove_front_matter(&ch.content);
-
-                    // Parse YAML
-                    if let Ok(metadata) = serde_yml::from_str::<ChapterMetadata>(&front_matter) {
-                        // Insert tables after step headers
-                        ch.content =
-                            insert_section_tables(&content_without_fm, &metadata.sections, &inventory);
-
-                        // Only accumulate into BOM if chapter is not excluded
-                        if metadata.exclude_from_bom {
-                            return;
-                        }
+        let config = BomConfig::from_book_config(&ctx.config)?;
+        build_bom(&ctx.root, &mut book, &config, Some(&ctx.renderer))?;
+        Ok(book)
+    }
+}
 
-                        // Accumulate all items from all sections for BOM
-                        for section_metadata in metadata.sections.values() {
-                            if let Some(input) = &section_metadata.input {
-                                let hardware =
-                                    input.hardware.as_deref().unwrap_or_default();
-                                let electronics =
-                                    input.electronics.as_deref().unwrap_or_default();
-                                let custom_parts =
-                                    input.custom_parts.as_deref().unwrap_or_default();
-                                let consumables =
-                                    input.consumables.as_deref().unwrap_or_default();
-                                let tools = input.tools.as_deref().unwrap_or_default();
-                                let assemblies =
-                                    input.assemblies.as_deref().unwrap_or_default();
-                                let subassemblies =
-                                    input.subassemblies.as_deref().unwrap_or_default();
-                                let units =
-                                    input.units.as_deref().unwrap_or_default();
-
-                                accumulate_fasteners(hardware, &inventory, &mut all_fasteners);
-                                accumulate_electronics(
-                                    electronics,
-                                    &inventory,
-                                    &mut all_electronics,
-                                );
-                                accumulate_custom_parts(
-                                    custom_parts,
-                                    &inventory,
-                                    &mut all_custom_parts,
-                                );
-                                accumulate_consumables(
-                                    consumables,
-                                    &inventory,
-                                    &mut all_consumables,
-                                );
-                                accumulate_tools(tools, &inventory, &mut all_tools);
-                                accumulate_assemblies(
-                                    assemblies,
-                                    &inventory,
-                                    &mut all_assemblies,
-                                );
-                                accumulate_subassemblies(
-                                    subassemblies,
-                                    &inventory,
-                                    &mut all_subassemblies,
-                                );
-                                accumulate_units(
-                                    units,
-                                    &inventory,
-                                    &mut all_units,
-                                );
-                            }
-                        }
+// How tables are rendered into chapter content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderMode {
+    Html,
+    // Plain markdown tables for renderers that do not understand HTML
+    Markdown,
+    // Only strip the front matter
+    None,
+}
+
+const DEFAULT_STEP_HEADER_PATTERN: &str = r"(?i)^##+\s+Step\s+(\d+(?:\.\d+)*):?.*$";
+
+// Settings from the `[preprocessor.bom]` table in book.toml
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+struct BomConfig {
+    // Renderers that get the interactive HTML tables
+    html_renderers: Vec<String>,
+    // Renderer whose run writes the BOM files; resolved in `from_book_config`
+    bom_renderer: Option<String>,
+    // Pin the workbook timestamp so unchanged content gives identical bytes
+    reproducible: bool,
+    // Regexes for step headers; the first capture group becomes the section key
+    step_header_patterns: Vec<String>,
+    // Append a generated parts catalog chapter
+    catalog: bool,
+    catalog_title: String,
+    // Units every torque setting is also shown in, e.g. `["in-lb"]`
+    torque_units: Vec<String>,
+    // Number of units built; required quantities are multiplied by it for the shortage report
+    build_quantity: u32,
+    // Icon and color overrides, keyed by front matter category
+    categories: BTreeMap<String, CategoryStyle>,
+    // Checkbox column for gathering the inputs of each step; ticks persist in HTML only
+    checklist: bool,
+    // Ticks are kept per version; defaults to a hash of each chapter's steps
+    checklist_version: Option<String>,
+    // The book's `src` directory, relative to the book root; taken from `[book]`
+    #[serde(skip)]
+    src_dir: std::path::PathBuf,
+    // Whether `output.html.additional-css` and `additional-js` include the assets
+    #[serde(skip)]
+    stylesheet_installed: bool,
+    #[serde(skip)]
+    script_installed: bool,
+}
+
+// `[preprocessor.bom.categories.<category>]`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+struct CategoryStyle {
+    // Shown before the table title; an empty string removes the icon
+    icon: Option<String>,
+    // Left border of the category's tables; `dark-color` applies to the coal, navy and ayu themes
+    color: Option<String>,
+    dark_color: Option<String>,
+}
+
+impl Default for BomConfig {
+    fn default() -> Self {
+        BomConfig {
+            html_renderers: vec!["html".to_string()],
+            bom_renderer: None,
+            reproducible: false,
+            step_header_patterns: vec![DEFAULT_STEP_HEADER_PATTERN.to_string()],
+            catalog: false,
+            catalog_title: "Parts Catalog".to_string(),
+            torque_units: Vec::new(),
+            build_quantity: 1,
+            categories: BTreeMap::new(),
+            checklist: false,
+            checklist_version: None,
+            src_dir: std::path::PathBuf::from("src"),
+            stylesheet_installed: false,
+            script_installed: false,
+        }
+    }
+}
+
+impl BomConfig {
+    fn from_book_config(book_config: &mdbook::Config) -> Result<Self, Error> {
+        let mut config: BomConfig = book_config
+            .get_deserialized_opt("preprocessor.bom")?
+            .unwrap_or_default();
+        config.src_dir = book_config.book.src.clone();
+        if config.build_quantity == 0 {
+            return Err(Error::msg(
+                "preprocessor.bom.build-quantity must be at least 1",
+            ));
+        }
+        config.torque_units = config
+            .torque_units
+            .iter()
+            .map(|unit| {
+                torque_unit(unit).map(str::to_string).ok_or_else(|| {
+                    Error::msg(format!(
+                        "Unknown unit '{}' in preprocessor.bom.torque-units, expected Nm, in-lb or ft-lb",
+                        unit
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        for (category, style) in &config.categories {
+            if !BOM_CATEGORIES.contains(&category.as_str()) {
+                return Err(Error::msg(format!(
+                    "Unknown category '{}' in preprocessor.bom.categories, expected one of {}",
+                    category,
+                    BOM_CATEGORIES.join(", ")
+                )));
+            }
+            // Colors end up in the stylesheet verbatim
+            for color in style.color.iter().chain(&style.dark_color) {
+                if color.is_empty() || color.contains([';', '{', '}', '<', '>', '\n']) {
+                    return Err(Error::msg(format!(
+                        "Invalid color '{}' in preprocessor.bom.categories.{}",
+                        color, category
+                    )));
+                }
+            }
+        }
+        config.stylesheet_installed =
+            asset_installed(book_config, "additional-css", STYLESHEET_FILE);
+        config.script_installed = asset_installed(book_config, "additional-js", SCRIPT_FILE);
+
+        // mdbook runs the preprocessor once per renderer, so the BOM files are
+        // only written for one of them: html if it is configured, else the first
+        if config.bom_renderer.is_none() {
+            let mut renderers: Vec<String> = book_config
+                .get("output")
+                .and_then(|output| output.as_table())
+                .map(|outputs| outputs.keys().cloned().collect())
+                .unwrap_or_default();
+            if renderers.is_empty() {
+                renderers.push("html".to_string());
+            }
+            // Respect `renderers = [...]` restricting which renderers run this preprocessor
+            if let Some(allowed) = book_config
+                .get("preprocessor.bom.renderers")
+                .and_then(|renderers| renderers.as_array())
+            {
+                renderers.retain(|r| allowed.iter().any(|a| a.as_str() == Some(r.as_str())));
+            }
+            config.bom_renderer = renderers
+                .iter()
+                .find(|r| *r == "html")
+                .or(renderers.first())
+                .cloned();
+        }
+
+        Ok(config)
+    }
+
+    fn step_matcher(&self) -> Result<StepMatcher, Error> {
+        StepMatcher::new(&self.step_header_patterns)
+    }
+
+    fn render_mode(&self, renderer: &str) -> RenderMode {
+        if self.html_renderers.iter().any(|r| r == renderer) {
+            RenderMode::Html
+        } else {
+            RenderMode::Markdown
+        }
+    }
+}
+
+const STYLESHEET_FILE: &str = "mdbook-bom.css";
+const STYLESHEET: &str = include_str!("../assets/mdbook-bom.css");
+const SCRIPT_FILE: &str = "mdbook-bom.js";
+const SCRIPT: &str = include_str!("../assets/mdbook-bom.js");
+
+// The bundled stylesheet followed by the category colors from book.toml
+fn stylesheet(config: &BomConfig) -> String {
+    let mut css = STYLESHEET.to_string();
+    let colors: Vec<String> = config
+        .categories
+        .iter()
+        .flat_map(|(category, style)| {
+            let class = category_class(category);
+            let light = style
+                .color
+                .as_ref()
+                .map(|color| format!(".bom-table.{} {{\n    --bom-table-color: {};\n}}\n", class, color));
+            let dark = style.dark_color.as_ref().map(|color| {
+                format!(
+                    ".coal .bom-table.{0},\n.navy .bom-table.{0},\n.ayu .bom-table.{0} {{\n    --bom-table-color: {1};\n}}\n",
+                    class, color
+                )
+            });
+            light.into_iter().chain(dark)
+        })
+        .collect();
+    if !colors.is_empty() {
+        css.push_str("\n/* Category colors from [preprocessor.bom.categories] */\n");
+        css.push_str(&colors.join("\n"));
+    }
+    css
+}
+
+// Only writes when the content changed, so `mdbook serve` does not rebuild in a loop
+fn write_asset(path: &std::path::Path, content: &str) -> Result<bool, Error> {
+    if std::fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        return Ok(false);
+    }
+    std::fs::write(path, content)
+        .map_err(|e| Error::msg(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(true)
+}
+
+// Whether `output.html.<key>` in book.toml lists the asset
+fn asset_installed(book_config: &mdbook::Config, key: &str, file: &str) -> bool {
+    book_config
+        .get(&format!("output.html.{}", key))
+        .and_then(|paths| paths.as_array())
+        .is_some_and(|paths| {
+            paths
+                .iter()
+                .any(|path| path.as_str().map(|p| p.trim_start_matches("./")) == Some(file))
+        })
+}
+
+fn load_inventory_from_env(book_root: &std::path::Path) -> Result<Inventory, Error> {
+    let excel_path = std::env::var("BOM_INVENTORY_FILE")
+        .map_err(|_| Error::msg("BOM_INVENTORY_FILE environment variable is required. Set it in .env file in the book directory."))?;
+
+    Inventory::load(&resolve_book_relative_path(&excel_path, book_root))
+}
+
+// Inserts tables into every chapter and writes all configured BOM outputs.
+// Without a renderer (standalone generation) no tables are inserted and the
+// outputs are always written. Returns the paths of the files that were written.
+fn build_bom(
+    book_root: &std::path::Path,
+    book: &mut Book,
+    config: &BomConfig,
+    renderer: Option<&str>,
+) -> Result<Vec<String>, Error> {
+    let mode = renderer.map_or(RenderMode::None, |r| config.render_mode(r));
+    // The HTML tables have no inline styles, so pages without the stylesheet would be unreadable
+    if mode == RenderMode::Html && !config.stylesheet_installed {
+        return Err(Error::msg(format!(
+            "{} is not in output.html.additional-css in book.toml, so the BOM tables would be unstyled. Run `mdbook-bom install` in the book's root directory to add it",
+            STYLESHEET_FILE
+        )));
+    }
+    let write_outputs = match renderer {
+        Some(r) => config.bom_renderer.as_deref() == Some(r),
+        None => true,
+    };
+
+    let matcher = config.step_matcher()?;
+
+    // Read configuration from environment variables (loaded from .env file)
+    let inventory = load_inventory_from_env(book_root)?;
+
+    let raw_output_path = std::env::var("BOM_OUTPUT_PATH")
+        .map_err(|_| Error::msg("BOM_OUTPUT_PATH environment variable is required. Set it in .env file in the book directory."))?;
+
+    let output_path = resolve_output_path(&raw_output_path, book_root)?;
+
+    // Everything but the rendering is collected first, in SUMMARY.md order; the
+    // overview tables need the totals to mark items that are short
+    let mut totals = BomTotals::default();
+    let mut catalog = PartsCatalog::default();
+    let mut chapters: Vec<ChapterExport> = Vec::new();
+    let mut kits: Vec<ChapterKit> = Vec::new();
+    let mut traveler: Vec<TravelerStep> = Vec::new();
+    let mut missing: Vec<MissingItem> = Vec::new();
+    let mut missing_images: BTreeSet<String> = BTreeSet::new();
+    let src_root = book_root.join(&config.src_dir);
+
+    for item in book.iter() {
+        if let BookItem::Chapter(ch) = item {
+            let metadata = match chapter_items(&ch.content, &matcher) {
+                Some((metadata, _)) => metadata,
+                None => {
+                    // Steps without any items still need a sign-off
+                    if extract_front_matter(&ch.content).is_none() {
+                        let metadata = ChapterMetadata::default();
+                        let steps = chapter_steps(&ch.content, &metadata, &matcher);
+                        traveler.extend(traveler_steps(
+                            &ch.name,
+                            &steps,
+                            &metadata,
+                            &inventory,
+                            &config.torque_units,
+                        ));
                     }
+                    continue;
+                }
+            };
+            let steps = chapter_steps(&ch.content, &metadata, &matcher);
+            traveler.extend(traveler_steps(
+                &ch.name,
+                &steps,
+                &metadata,
+                &inventory,
+                &config.torque_units,
+            ));
+
+            if config.catalog {
+                catalog.record(&ch.name, ch.path.as_deref(), &metadata, &inventory);
+            }
+            collect_missing_images(&metadata, &inventory, &src_root, &mut missing_images);
+
+            // Record chapter for JSON export, including excluded chapters
+            chapters.push(export_chapter(
+                &ch.name,
+                ch.path.as_deref(),
+                &metadata,
+                &inventory,
+                &mut missing,
+            ));
+
+            // Only accumulate into BOM if chapter is not excluded
+            if !metadata.exclude_from_bom {
+                totals.accumulate_chapter(
+                    &ch.name,
+                    ch.path.as_deref(),
+                    &steps,
+                    &metadata,
+                    &inventory,
+                );
+                let kit = chapter_kit(&ch.name, &metadata, &inventory);
+                if !kit.items.is_empty() {
+                    kits.push(kit);
+                }
+            }
+        }
+    }
+    let shortages = find_shortages(&totals, &inventory, config.build_quantity);
+    book.for_each_mut(|item: &mut BookItem| {
+        if let BookItem::Chapter(ch) = item {
+            let (metadata, declarations) = match chapter_items(&ch.content, &matcher) {
+                Some(items) => items,
+                None => {
+                    // Part references also work in chapters without any items
+                    if extract_front_matter(&ch.content).is_none() && mode != RenderMode::None {
+                        ch.content = expand_part_references(
+                            &ch.content,
+                            &IndexMap::new(),
+                            &inventory,
+                            &matcher,
+                            mode,
+                        );
+                    }
+                    return;
+                }
+            };
+            for declaration in &declarations {
+                match (&declaration.item, &declaration.step_key) {
+                    (Err(e), _) => warn!("{}: invalid declaration: {}", ch.name, e),
+                    (Ok(item), None) => warn!(
+                        "{}: '{}' is declared outside of a step and is ignored",
+                        ch.name, item.name
+                    ),
+                    _ => {}
                 }
             }
-        });
 
-        // Create directory for output file
-        create_output_directory_for_path(&output_path)?;
-
-        // Generate BOM Excel file
-        generate_bom_excel_file(
-            &all_fasteners,
-            &all_electronics,
-            &all_custom_parts,
-            &all_consumables,
-            &all_tools,
-            &all_assemblies,
-            &all_subassemblies,
-            &all_units,
-            &output_path,
+            // Remove front matter and inline declarations from content
+            let content_without_fm = replace_inline_declarations(&remove_front_matter(&ch.content));
+
+            // Without `checklist-version`, ticks are kept until the chapter's steps change
+            let checklist_version = match (config.checklist, &config.checklist_version) {
+                (false, _) => None,
+                (true, Some(version)) => Some(version.clone()),
+                (true, None) => Some(steps_hash(&metadata)),
+            };
+
+            // Insert tables after step headers
+            let options = TableOptions::for_chapter(
+                mode,
+                ch.path.as_deref(),
+                config,
+                &shortages,
+                checklist_version.as_deref(),
+            );
+            ch.content = match mode {
+                RenderMode::Html | RenderMode::Markdown => insert_section_tables(
+                    &expand_part_references(
+                        &content_without_fm,
+                        &metadata.sections,
+                        &inventory,
+                        &matcher,
+                        mode,
+                    ),
+                    &metadata.sections,
+                    &inventory,
+                    &matcher,
+                    &options,
+                ),
+                RenderMode::None => content_without_fm,
+            };
+        }
+    });
+
+    for image in &missing_images {
+        warn!("Image file not found: {}", src_root.join(image).display());
+    }
+
+    // Keep the installed assets in sync with this version and the category colors in book.toml
+    if mode == RenderMode::Html {
+        write_asset(&book_root.join(STYLESHEET_FILE), &stylesheet(config))?;
+        if config.script_installed {
+            write_asset(&book_root.join(SCRIPT_FILE), SCRIPT)?;
+        } else {
+            warn!(
+                "{} is not in output.html.additional-js, so the tables have no Show All buttons, checklist or build quantity input; run `mdbook-bom install` to enable them",
+                SCRIPT_FILE
+            );
+        }
+    }
+
+    if config.catalog && mode != RenderMode::None {
+        book.push_item(BookItem::Chapter(Chapter::new(
+            &config.catalog_title,
+            catalog.render(&config.catalog_title, &config.categories),
+            CATALOG_PATH,
+            Vec::new(),
+        )));
+    }
+
+    if !write_outputs {
+        return Ok(Vec::new());
+    }
+
+    // Create directory for output file
+    create_output_directory_for_path(&output_path)?;
+
+    // Generate BOM Excel file
+    let created_at = workbook_timestamp(config.reproducible)?;
+    generate_bom_excel_file(
+        &totals,
+        &shortages,
+        &kits,
+        &config.torque_units,
+        &output_path,
+        created_at,
+    )?;
+    let mut written = vec![output_path];
+
+    // Generate optional JSON export of the full data model
+    if let Ok(raw_json_path) = std::env::var("BOM_JSON_OUTPUT_PATH") {
+        let json_path = resolve_output_path(&raw_json_path, book_root)?;
+        create_output_directory_for_path(&json_path)?;
+        generate_bom_json_file(
+            &chapters,
+            &totals,
+            config.build_quantity,
+            &shortages,
+            &missing,
+            &json_path,
         )?;
+        written.push(json_path);
+    }
 
-        Ok(book)
+    // Generate optional shop-floor traveler
+    if let Ok(raw_traveler_path) = std::env::var("BOM_TRAVELER_PATH") {
+        let traveler_path = resolve_output_path(&raw_traveler_path, book_root)?;
+        create_output_directory_for_path(&traveler_path)?;
+        generate_traveler_excel_file(&traveler, &traveler_path, created_at)?;
+        written.push(traveler_path);
+    }
+
+    Ok(written)
+}
+
+// Front matter of a chapter merged with its inline declarations. `None` when the
+// chapter has neither, or when its front matter does not parse.
+fn chapter_items(
+    content: &str,
+    matcher: &StepMatcher,
+) -> Option<(ChapterMetadata, Vec<InlineDeclaration>)> {
+    let front_matter = extract_front_matter(content);
+    let mut metadata = match &front_matter {
+        Some(front_matter) => serde_yml::from_str::<ChapterMetadata>(front_matter).ok()?,
+        None => ChapterMetadata::default(),
+    };
+
+    let body = remove_front_matter(content);
+    let declarations = find_inline_declarations(&body, &metadata.sections, matcher);
+    if front_matter.is_none() && declarations.is_empty() {
+        return None;
+    }
+    if !declarations.is_empty() {
+        let step_headers = find_step_headers(&body, &metadata.sections, matcher);
+        merge_inline_declarations(&mut metadata, &declarations, &step_headers);
     }
+    Some((metadata, declarations))
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Front matter of an mdbook-bom chapter
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
 struct ChapterMetadata {
+    /// Render tables for this chapter but leave its items out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
-    sections: std::collections::HashMap<String, SectionMetadata>,
+    /// Sections keyed by step, e.g. `step_1` for `## Step 1` and `step_1_2` for `### Step 1.2`
+    sections: IndexMap<String, SectionMetadata>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct SectionMetadata {
+    /// Components and tools required by the step
     input: Option<InputMetadata>,
+    /// Parts produced by the step
     output: Option<OutputMetadata>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, JsonSchema)]
 struct InputMetadata {
+    /// Names from the inventory's Hardware sheet
     hardware: Option<Vec<PartReference>>,
+    /// Names from the inventory's Electronics sheet
     electronics: Option<Vec<PartReference>>,
+    /// Names from the inventory's Custom Parts sheet
     custom_parts: Option<Vec<PartReference>>,
+    /// Names from the inventory's Consumables sheet
     consumables: Option<Vec<ConsumableReference>>,
+    /// Names from the inventory's Tools sheet
     tools: Option<Vec<ToolReference>>,
+    /// Names from the inventory's Assemblies sheet
     assemblies: Option<Vec<AssemblyReference>>,
+    /// Names from the inventory's Subassemblies sheet
     subassemblies: Option<Vec<SubassemblyReference>>,
+    /// Names from the inventory's Units sheet
     units: Option<Vec<UnitReference>>,
 }
 
 // Simplified front matter structures
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct PartReference {
     name: String,
     quantity: u32,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct ConsumableReference {
     name: String,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct ToolReference {
     name: String,
-    setting: Option<String>,
+    /// Setting to use for this step, e.g. "5 Nm" or `{value: 5, unit: Nm}`
+    setting: Option<ToolSetting>,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Tool setting: a value with a unit, or free text such as "Speed 3"
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, JsonSchema)]
+#[serde(untagged)]
+enum ToolSetting {
+    Measured {
+        value: f64,
+        /// Torque units (Nm, in-lb, ft-lb) can be converted in the tables
+        unit: String,
+    },
+    Text(String),
+}
+
+// Canonical torque units and how many Nm one of them is
+const TORQUE_UNITS: [(&str, f64); 3] = [
+    ("Nm", 1.0),
+    ("in-lb", 0.112_984_829),
+    ("ft-lb", 1.355_817_948),
+];
+
+// Canonical name of a torque unit, accepting the usual spellings. The Nm
+// abbreviation needs a capital N, as "nm" is a nanometre.
+fn torque_unit(text: &str) -> Option<&'static str> {
+    let unit: String = text
+        .to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '·' | '.' | '*'))
+        .collect();
+    match unit.trim_end_matches('s') {
+        "nm" if text.trim_start().starts_with('N') => Some("Nm"),
+        "newtonmeter" | "newtonmetre" => Some("Nm"),
+        "inlb" | "inlbf" | "lbin" | "lbfin" | "inchpound" => Some("in-lb"),
+        "ftlb" | "ftlbf" | "lbft" | "lbfft" | "footpound" => Some("ft-lb"),
+        _ => None,
+    }
+}
+
+// A number followed by a unit, for torques written as text
+static MEASURED_TEXT_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+impl ToolSetting {
+    // Torques written as text, e.g. "5Nm" or "5 N·m", become measured settings,
+    // so they compare equal to "5 Nm". Other text such as "3000 rpm" stays as written.
+    fn normalized(&self) -> ToolSetting {
+        match self {
+            ToolSetting::Measured { value, unit } => ToolSetting::Measured {
+                value: *value,
+                unit: torque_unit(unit).map_or_else(|| unit.trim().to_string(), str::to_string),
+            },
+            ToolSetting::Text(text) => {
+                let re = MEASURED_TEXT_RE.get_or_init(|| {
+                    regex::Regex::new(r"^\s*([+-]?\d+(?:\.\d+)?)\s*([^\d\s].*?)\s*$").unwrap()
+                });
+                let measured = re.captures(text).and_then(|caps| {
+                    Some((
+                        caps[1].parse::<f64>().ok()?,
+                        torque_unit(&caps[2])?.to_string(),
+                    ))
+                });
+                match measured {
+                    Some((value, unit)) => ToolSetting::Measured { value, unit },
+                    None => ToolSetting::Text(text.trim().to_string()),
+                }
+            }
+        }
+    }
+
+    // Text for the tables, with torques also shown in the configured units,
+    // e.g. "5 Nm (44.3 in-lb)". Only the conversions are rounded.
+    fn display(&self, torque_units: &[String]) -> String {
+        match self.normalized() {
+            ToolSetting::Measured { value, unit } => {
+                let mut text = format!("{} {}", value, unit);
+                let to_nm = TORQUE_UNITS
+                    .iter()
+                    .find(|(u, _)| *u == unit)
+                    .map(|(_, to_nm)| *to_nm);
+                if let Some(to_nm) = to_nm {
+                    let conversions: Vec<String> = TORQUE_UNITS
+                        .iter()
+                        .filter(|(target, _)| {
+                            *target != unit && torque_units.iter().any(|u| u == target)
+                        })
+                        .map(|(target, target_to_nm)| {
+                            format!(
+                                "{} {}",
+                                format_value(value * to_nm / target_to_nm, 1),
+                                target
+                            )
+                        })
+                        .collect();
+                    if !conversions.is_empty() {
+                        text.push_str(&format!(" ({})", conversions.join(", ")));
+                    }
+                }
+                text
+            }
+            ToolSetting::Text(text) => text,
+        }
+    }
+}
+
+// Rounded to at most `decimals` places, without trailing zeros
+fn format_value(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct AssemblyReference {
     name: String,
     quantity: u32,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct SubassemblyReference {
     name: String,
     quantity: u32,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct UnitReference {
     name: String,
     quantity: u32,
+    /// Show in the tables but leave out of the BOM
     #[serde(default)]
     exclude_from_bom: bool,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct OutputReference {
     name: String,
     quantity: u32,
+    /// Leave out of the chapter overview tables
     #[serde(default)]
     exclude_from_overview: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 struct OutputMetadata {
+    /// Names from the inventory's Custom Parts sheet
     custom_parts: Option<Vec<OutputReference>>,
+    /// Names from the inventory's Assemblies sheet
     assemblies: Option<Vec<OutputReference>>,
+    /// Names from the inventory's Subassemblies sheet
     subassemblies: Option<Vec<OutputReference>>,
+    /// Names from the inventory's Units sheet
     units: Option<Vec<OutputReference>>,
 }
 
+// Bin locations and similar codes may be typed as numbers in the spreadsheet
+fn deserialize_cell_as_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = calamine::Data::deserialize(deserializer)?.to_string();
+    Ok(Some(text.trim().to_string()).filter(|text| !text.is_empty()))
+}
+
 // Inventory structures
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryFastener {
-    #[serde(rename = "Name")]
+    #[serde(rename(serialize = "name", deserialize = "Name"))]
     part_number: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryElectronic {
-    #[serde(rename = "Name")]
+    #[serde(rename(serialize = "name", deserialize = "Name"))]
     part_number: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryCustomPart {
-    #[serde(rename = "Name")]
+    #[serde(rename(serialize = "name", deserialize = "Name"))]
     part_number: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryConsumable {
-    #[serde(rename = "Name")]
+    #[serde(rename(serialize = "name", deserialize = "Name"))]
     part_number: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryTool {
-    #[serde(rename = "Name")]
+    #[serde(rename(deserialize = "Name"))]
     name: String,
-    #[serde(rename = "Brand", default)]
+    #[serde(rename(deserialize = "Brand"), default)]
     brand: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryAssembly {
-    #[serde(rename = "Name")]
+    #[serde(rename(deserialize = "Name"))]
     name: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventorySubassembly {
-    #[serde(rename = "Name")]
+    #[serde(rename(deserialize = "Name"))]
     name: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct InventoryUnit {
-    #[serde(rename = "Name")]
+    #[serde(rename(deserialize = "Name"))]
     name: String,
-    #[serde(rename = "Description", default)]
+    #[serde(rename(deserialize = "Description"), default)]
     description: Option<String>,
+    #[serde(
+        rename(deserialize = "Image"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    image: Option<String>,
+    #[serde(
+        rename(deserialize = "Datasheet"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    datasheet: Option<String>,
+    #[serde(
+        rename(deserialize = "URL"),
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    url: Option<String>,
+    #[serde(
+        rename(deserialize = "On Hand"),
+        default,
+        deserialize_with = "calamine::deserialize_as_f64_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    on_hand: Option<f64>,
+    #[serde(
+        rename(deserialize = "Bin"),
+        default,
+        deserialize_with = "deserialize_cell_as_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bin: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomFastenerItem {
+    #[serde(rename = "name")]
     part_number: String,
     description: String,
     #[allow(dead_code)]
+    #[serde(skip)]
     supplier: String,
     total_quantity: u32,
     #[allow(dead_code)]
+    #[serde(skip)]
     unit_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomElectronicItem {
+    #[serde(rename = "name")]
     part_number: String,
     description: String,
     #[allow(dead_code)]
+    #[serde(skip)]
     supplier: String,
     total_quantity: u32,
     #[allow(dead_code)]
+    #[serde(skip)]
     unit_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomCustomPartItem {
+    #[serde(rename = "name")]
     part_number: String,
     description: String,
     #[allow(dead_code)]
+    #[serde(skip)]
     supplier: String,
     total_quantity: u32,
     #[allow(dead_code)]
+    #[serde(skip)]
     unit_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomConsumableItem {
+    #[serde(rename = "name")]
     part_number: String,
     description: String,
     #[allow(dead_code)]
+    #[serde(skip)]
     supplier: String,
     #[allow(dead_code)]
+    #[serde(skip)]
     unit_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomToolItem {
     name: String,
     brand: String,
-    settings: Vec<String>, // Multiple settings from different chapters
+    settings: Vec<ToolSetting>, // Multiple settings from different chapters
+    usage: Vec<ToolUsage>,
+    step_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+// Steps using a tool with one setting, `None` for steps that give no setting
+#[derive(Debug, Clone, Serialize)]
+struct ToolUsage {
+    setting: Option<ToolSetting>,
+    steps: Vec<String>,
+    // Chapter path and section key of each step, in the order of `steps`
+    #[serde(skip)]
+    step_ids: Vec<(String, String)>,
+}
+
+// A step of the book, identified by its chapter's path and its section key. The
+// label, e.g. `Assembly › Step 2`, is only for display, as titles can repeat.
+struct StepRef {
+    id: (String, String),
+    label: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
 struct BomAssemblyItem {
     name: String,
     description: String,
     total_quantity: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomSubassemblyItem {
     name: String,
     description: String,
     total_quantity: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct BomUnitItem {
     name: String,
     description: String,
     total_quantity: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datasheet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+// Book-wide totals per category, keyed by inventory name
+#[derive(Debug, Default)]
+struct BomTotals {
+    fasteners: HashMap<String, BomFastenerItem>,
+    electronics: HashMap<String, BomElectronicItem>,
+    custom_parts: HashMap<String, BomCustomPartItem>,
+    consumables: HashMap<String, BomConsumableItem>,
+    tools: HashMap<String, BomToolItem>,
+    assemblies: HashMap<String, BomAssemblyItem>,
+    subassemblies: HashMap<String, BomSubassemblyItem>,
+    units: HashMap<String, BomUnitItem>,
+    // Quantities made by the book's own steps, by category and name
+    produced: HashMap<(&'static str, String), u32>,
+}
+
+impl BomTotals {
+    fn accumulate_chapter(
+        &mut self,
+        chapter: &str,
+        path: Option<&std::path::Path>,
+        steps: &[(String, String)],
+        metadata: &ChapterMetadata,
+        inventory: &Inventory,
+    ) {
+        for (key, section_metadata) in &metadata.sections {
+            if let Some(input) = &section_metadata.input {
+                let title = steps
+                    .iter()
+                    .find(|(_, step_key)| step_key == key)
+                    .map_or(key.as_str(), |(title, _)| title.as_str());
+                let step = StepRef {
+                    id: (
+                        path.map_or_else(
+                            || chapter.to_string(),
+                            |path| path.to_string_lossy().into_owned(),
+                        ),
+                        key.clone(),
+                    ),
+                    label: format!("{} › {}", chapter, title),
+                };
+                self.accumulate(input, inventory, &step);
+            }
+            if let Some(output) = &section_metadata.output {
+                self.accumulate_output(output);
+            }
+        }
+    }
+
+    fn accumulate_output(&mut self, output: &OutputMetadata) {
+        let outputs = [
+            ("custom_parts", &output.custom_parts),
+            ("assemblies", &output.assemblies),
+            ("subassemblies", &output.subassemblies),
+            ("units", &output.units),
+        ];
+        for (category, items) in outputs {
+            for item in items.as_deref().unwrap_or_default() {
+                *self
+                    .produced
+                    .entry((category, item.name.clone()))
+                    .or_insert(0) += item.quantity;
+            }
+        }
+    }
+
+    fn accumulate(&mut self, input: &InputMetadata, inventory: &Inventory, step: &StepRef) {
+        let hardware = input.hardware.as_deref().unwrap_or_default();
+        let electronics = input.electronics.as_deref().unwrap_or_default();
+        let custom_parts = input.custom_parts.as_deref().unwrap_or_default();
+        let consumables = input.consumables.as_deref().unwrap_or_default();
+        let tools = input.tools.as_deref().unwrap_or_default();
+        let assemblies = input.assemblies.as_deref().unwrap_or_default();
+        let subassemblies = input.subassemblies.as_deref().unwrap_or_default();
+        let units = input.units.as_deref().unwrap_or_default();
+
+        accumulate_fasteners(hardware, inventory, &mut self.fasteners);
+        accumulate_electronics(electronics, inventory, &mut self.electronics);
+        accumulate_custom_parts(custom_parts, inventory, &mut self.custom_parts);
+        accumulate_consumables(consumables, inventory, &mut self.consumables);
+        accumulate_tools(tools, inventory, step, &mut self.tools);
+        accumulate_assemblies(assemblies, inventory, &mut self.assemblies);
+        accumulate_subassemblies(subassemblies, inventory, &mut self.subassemblies);
+        accumulate_units(units, inventory, &mut self.units);
+    }
 }
 
 fn extract_front_matter(content: &str) -> Option<String> {
@@ -712,215 +2139,1155 @@ fn remove_front_matter(content: &str) -> String {
     content.to_string()
 }
 
-fn find_step_headers(content: &str) -> Vec<(String, usize)> {
-    use regex::Regex;
-    let re = Regex::new(r"(?i)^##+\s+Step\s+(\d+):?.*$").unwrap();
+// Decides which markdown headers start a step and which section they belong to
+struct StepMatcher {
+    patterns: Vec<regex::Regex>,
+}
 
-    content
-        .lines()
-        .enumerate()
-        .filter_map(|(line_idx, line)| {
-            re.captures(line).map(|caps| {
-                let step_num = caps.get(1).unwrap().as_str();
-                let step_key = format!("step_{}", step_num);
-                (step_key, line_idx)
+impl StepMatcher {
+    fn new(patterns: &[String]) -> Result<Self, Error> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                regex::Regex::new(pattern).map_err(|e| {
+                    Error::msg(format!("Invalid step header pattern '{}': {}", pattern, e))
+                })
             })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StepMatcher { patterns })
+    }
+
+    // Key from the first pattern matching the header, e.g. `## Step 3` -> `step_3`
+    // and `## Step 3.4` -> `step_3_4`
+    fn pattern_key(&self, line: &str) -> Option<String> {
+        self.patterns.iter().find_map(|re| {
+            let caps = re.captures(line)?;
+            let captured = caps.get(1).unwrap_or_else(|| caps.get(0).unwrap()).as_str();
+            let key = normalize_section_key(&captured.replace('.', "_"));
+            if key.starts_with(|c: char| c.is_ascii_digit()) {
+                Some(format!("step_{}", key))
+            } else {
+                Some(key)
+            }
+        })
+    }
+}
+
+impl Default for StepMatcher {
+    fn default() -> Self {
+        StepMatcher::new(&[DEFAULT_STEP_HEADER_PATTERN.to_string()]).expect("Valid default pattern")
+    }
+}
+
+// Section keys match case-insensitively, with `-` and `_` treated alike
+fn normalize_section_key(key: &str) -> String {
+    key.trim().to_lowercase().replace('-', "_")
+}
+
+// Anchor mdbook generates for a header: lowercase, spaces become `-`,
+// punctuation other than `-` and `_` is dropped
+fn header_slug(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
         })
         .collect()
 }
 
-fn insert_section_tables(
+struct Heading {
+    level: usize,
+    line_idx: usize,
+    end_line_idx: usize,
+    // The header as an ATX line (`## Title {#id}`), also for setext headers
+    line: String,
+}
+
+// Headings as mdbook's markdown parser sees them, so `#` lines inside code
+// blocks and HTML blocks are not mistaken for headers
+fn find_headings(content: &str) -> Vec<Heading> {
+    use pulldown_cmark::{Event, Tag};
+
+    let line_of = |offset: usize| content[..offset].matches('\n').count();
+
+    mdbook::utils::new_cmark_parser(content, false)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let level = level as usize;
+                let source = content[range.clone()].trim_end();
+                let first_line = source.lines().next().unwrap_or_default().trim();
+                let line = if first_line.starts_with('#') {
+                    first_line.to_string()
+                } else {
+                    format!("{} {}", "#".repeat(level), first_line)
+                };
+                Some(Heading {
+                    level,
+                    line_idx: line_of(range.start),
+                    end_line_idx: line_of(range.start + source.len()),
+                    line,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Byte ranges of code blocks and inline code spans, where directives are
+// documentation rather than declarations
+fn code_ranges(content: &str) -> Vec<std::ops::Range<usize>> {
+    use pulldown_cmark::{Event, Tag};
+
+    mdbook::utils::new_cmark_parser(content, false)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+fn in_code(code: &[std::ops::Range<usize>], offset: usize) -> bool {
+    code.iter().any(|range| range.contains(&offset))
+}
+
+struct StepHeader {
+    key: String,
+    line_idx: usize,
+    // Last line of the header; setext headers span two lines
+    end_line_idx: usize,
+    // Number of `#`s
+    level: usize,
+    // Nested under another step header
+    is_sub_step: bool,
+    // Header text without the `#`s and any `{#id}`
+    title: String,
+    // Key a nested numbered header would have on its own, e.g. `step_2` for
+    // `step_1_2`; `check` reports it when only that section exists
+    unqualified_key: Option<String>,
+}
+
+// An ATX header with an optional `{#id}`
+static HEADER_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+// A plain numbered step key, which is qualified when the step is nested
+static NUMBERED_STEP_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+// Finds step headers and the section key each one belongs to. A header
+// belongs to a section when its `{#id}`, its pattern key or its slug matches
+// the section key. Headers that only match a pattern are returned with the
+// pattern key so unmatched steps can still be reported.
+//
+// A numbered step nested under another step is qualified with the parent's
+// key, so `### Step 2` under `## Step 1` is `step_1_2`. It never falls back to
+// the plain `step_2`, which belongs to the top-level `## Step 2`.
+fn find_step_headers(
     content: &str,
-    sections: &std::collections::HashMap<String, SectionMetadata>,
-    inventory: &Inventory,
-) -> String {
-    let step_headers = find_step_headers(content);
-    let lines: Vec<&str> = content.lines().collect();
-    let mut result = Vec::new();
-    let mut overview_inserted = false;
-    let mut pending_output: Option<String> = None;
+    sections: &IndexMap<String, SectionMetadata>,
+    matcher: &StepMatcher,
+) -> Vec<StepHeader> {
+    use regex::Regex;
+    let header_re = HEADER_RE
+        .get_or_init(|| Regex::new(r"^(##+)\s+(.*?)\s*(?:\{#([^}\s]+)[^}]*\})?\s*$").unwrap());
+    let numbered_re = NUMBERED_STEP_RE.get_or_init(|| Regex::new(r"^step_(\d+)$").unwrap());
 
-    // Generate overview tables (without header)
-    let overview_section = generate_overview_tables(sections, inventory);
+    let section_keys: Vec<(String, &String)> = sections
+        .keys()
+        .map(|key| (normalize_section_key(key), key))
+        .collect();
+    let find_section = |candidate: &str| {
+        let candidate = normalize_section_key(candidate);
+        section_keys
+            .iter()
+            .find(|(normalized, _)| *normalized == candidate)
+            .map(|(_, key)| (*key).clone())
+    };
 
-    for (line_idx, line) in lines.iter().enumerate() {
-        // Check if this is a top-level header (# Header) and insert overview after it
-        if !overview_inserted && line.starts_with("# ") && !line.starts_with("## ") {
-            result.push(line.to_string());
+    // Enclosing step headers as (level, key)
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    let mut headers = Vec::new();
 
-            // Insert overview tables after the top-level header
-            if !overview_section.trim().is_empty() {
-                result.push("".to_string()); // Empty line
-                result.extend(overview_section.lines().map(|s| s.to_string()));
-                result.push("".to_string()); // Empty line after overview
+    for heading in find_headings(content) {
+        let line = heading.line.as_str();
+        let caps = match header_re.captures(line) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let level = caps[1].len();
+        while parents
+            .last()
+            .is_some_and(|(parent_level, _)| *parent_level >= level)
+        {
+            parents.pop();
+        }
+
+        let mut unqualified_key = None;
+        let pattern_key = matcher.pattern_key(line).map(|key| {
+            match (numbered_re.captures(&key), parents.last()) {
+                (Some(number), Some((_, parent_key))) => {
+                    let qualified = format!("{}_{}", parent_key, &number[1]);
+                    unqualified_key = Some(key);
+                    qualified
+                }
+                _ => key,
             }
-            overview_inserted = true;
-            continue;
+        });
+        let explicit_id = caps.get(3).and_then(|id| find_section(id.as_str()));
+        // The slug of a nested `### Step 2` is the unqualified key as well
+        let slug_key = find_section(&header_slug(&caps[2]))
+            .filter(|key| Some(key) != unqualified_key.as_ref());
+        let step_key = match explicit_id
+            .or_else(|| pattern_key.as_deref().and_then(find_section))
+            .or(slug_key)
+            .or(pattern_key)
+        {
+            Some(key) => key,
+            None => continue,
+        };
+
+        headers.push(StepHeader {
+            key: step_key.clone(),
+            line_idx: heading.line_idx,
+            end_line_idx: heading.end_line_idx,
+            level,
+            is_sub_step: !parents.is_empty(),
+            title: caps[2].to_string(),
+            unqualified_key,
+        });
+        parents.push((level, step_key));
+    }
+
+    headers
+}
+
+// What a step's tables list: its own section, merged with those of its
+// sub-steps when it has any
+fn step_section_metadata(
+    step_headers: &[StepHeader],
+    header_idx: usize,
+    sections: &IndexMap<String, SectionMetadata>,
+) -> Option<SectionMetadata> {
+    let header = &step_headers[header_idx];
+    let mut step_sections: Vec<&SectionMetadata> = sections.get(&header.key).into_iter().collect();
+    step_sections.extend(
+        step_headers[header_idx + 1..]
+            .iter()
+            .take_while(|h| h.level > header.level)
+            .filter_map(|h| sections.get(&h.key)),
+    );
+    match step_sections.len() {
+        0 => None,
+        1 => Some(step_sections[0].clone()),
+        _ => Some(merge_sections(&step_sections)),
+    }
+}
+
+// A step's own items merged with those of all its sub-steps
+fn merge_sections(sections: &[&SectionMetadata]) -> SectionMetadata {
+    let mut input = InputMetadata::default();
+    let mut outputs = Vec::new();
+
+    fn extend<T: Clone>(target: &mut Option<Vec<T>>, items: &Option<Vec<T>>) {
+        if let Some(items) = items {
+            target
+                .get_or_insert_with(Vec::new)
+                .extend(items.iter().cloned());
+        }
+    }
+
+    for section in sections {
+        if let Some(section_input) = &section.input {
+            extend(&mut input.hardware, &section_input.hardware);
+            extend(&mut input.electronics, &section_input.electronics);
+            extend(&mut input.custom_parts, &section_input.custom_parts);
+            extend(&mut input.consumables, &section_input.consumables);
+            extend(&mut input.tools, &section_input.tools);
+            extend(&mut input.assemblies, &section_input.assemblies);
+            extend(&mut input.subassemblies, &section_input.subassemblies);
+            extend(&mut input.units, &section_input.units);
         }
+        if let Some(output) = &section.output {
+            outputs.push(output.clone());
+        }
+    }
+
+    input.hardware = input.hardware.map(|h| combine_parts(&h));
+    input.electronics = input.electronics.map(|e| combine_parts(&e));
+    input.custom_parts = input.custom_parts.map(|c| combine_parts(&c));
+    input.consumables = input.consumables.map(|c| deduplicate_consumables(&c));
+    // Tools are combined per name when their table is rendered
+    input.assemblies = input.assemblies.map(|a| combine_assemblies(&a));
+    input.subassemblies = input.subassemblies.map(|s| combine_subassemblies(&s));
+    input.units = input.units.map(|u| combine_units(&u));
+
+    SectionMetadata {
+        input: Some(input),
+        output: if outputs.is_empty() {
+            None
+        } else {
+            Some(combine_output_metadata(&outputs))
+        },
+    }
+}
+
+// `{{#use CATEGORY NAME [xN]}}` or `{{#tool NAME [SETTING]}}`, optionally escaped with a leading backslash
+const INLINE_DECLARATION_PATTERN: &str = r"(\\?)\{\{#(use|tool)\s+([^}]*?)\s*\}\}";
+static INLINE_DECLARATION_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+fn inline_declaration_re() -> &'static regex::Regex {
+    INLINE_DECLARATION_RE.get_or_init(|| regex::Regex::new(INLINE_DECLARATION_PATTERN).unwrap())
+}
+
+// An item declared in the step text instead of the front matter
+struct InlineDeclaration {
+    line_idx: usize,
+    // Innermost enclosing step; None before the first step header
+    step_key: Option<String>,
+    item: Result<InlineItem, String>,
+}
+
+struct InlineItem {
+    category: &'static str,
+    name: String,
+    quantity: u32,
+    setting: Option<String>,
+}
+
+fn parse_inline_declaration(kind: &str, args: &str) -> Result<InlineItem, String> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+
+    if kind == "tool" {
+        let (name, setting) = args
+            .split_first()
+            .ok_or_else(|| "Tool declaration needs a name: {{#tool NAME [SETTING]}}".to_string())?;
+        return Ok(InlineItem {
+            category: "tools",
+            name: name.to_string(),
+            quantity: 1,
+            setting: if setting.is_empty() {
+                None
+            } else {
+                Some(setting.join(" "))
+            },
+        });
+    }
+
+    let (category, name, quantity) = match args.as_slice() {
+        [category, name] => (*category, *name, None),
+        [category, name, quantity] => (*category, *name, Some(*quantity)),
+        _ => return Err("Expected {{#use CATEGORY NAME [xN]}}".to_string()),
+    };
+    let category = BOM_CATEGORIES
+        .iter()
+        .copied()
+        .find(|c| *c == category)
+        .ok_or_else(|| {
+            format!(
+                "Unknown category '{}', expected one of {}",
+                category,
+                BOM_CATEGORIES.join(", ")
+            )
+        })?;
+    let quantity = match quantity {
+        Some(quantity) => quantity
+            .strip_prefix('x')
+            .and_then(|n| n.parse::<u32>().ok())
+            .ok_or_else(|| format!("Invalid quantity '{}', expected e.g. x4", quantity))?,
+        None => 1,
+    };
+
+    Ok(InlineItem {
+        category,
+        name: name.to_string(),
+        quantity,
+        setting: None,
+    })
+}
+
+fn find_inline_declarations(
+    content: &str,
+    sections: &IndexMap<String, SectionMetadata>,
+    matcher: &StepMatcher,
+) -> Vec<InlineDeclaration> {
+    let re = inline_declaration_re();
+    if !re.is_match(content) {
+        return Vec::new();
+    }
+    let step_headers = find_step_headers(content, sections, matcher);
+    let code = code_ranges(content);
+
+    re.captures_iter(content)
+        .filter(|caps| caps[1].is_empty())
+        .filter_map(|caps| {
+            let start = caps.get(0).unwrap().start();
+            if in_code(&code, start) {
+                return None;
+            }
+            let line_idx = content[..start].matches('\n').count();
+            let step_key = step_headers
+                .iter()
+                .rev()
+                .find(|h| h.line_idx <= line_idx)
+                .map(|h| h.key.clone());
+            Some(InlineDeclaration {
+                line_idx,
+                step_key,
+                item: parse_inline_declaration(&caps[2], &caps[3]),
+            })
+        })
+        .collect()
+}
 
-        // Check if this line is a step header and add horizontal rule before it (but not the first step)
-        let is_step_header = step_headers
+// Adds valid declarations to the sections of their steps, creating sections as needed.
+// A new section goes before the first section whose header comes later in the
+// chapter, so sections stay in document order.
+fn merge_inline_declarations(
+    metadata: &mut ChapterMetadata,
+    declarations: &[InlineDeclaration],
+    step_headers: &[StepHeader],
+) {
+    let header_line = |key: &str| {
+        step_headers
             .iter()
-            .any(|(_, header_line_idx)| line_idx == *header_line_idx);
-        if is_step_header && line_idx > 0 {
-            // Flush pending output table before the horizontal rule of the next step
-            if let Some(output_table) = pending_output.take() {
-                result.push("".to_string());
-                result.extend(output_table.lines().map(|s| s.to_string()));
-                result.push("".to_string());
+            .find(|h| h.key == key)
+            .map(|h| h.line_idx)
+    };
+
+    for declaration in declarations {
+        let (step_key, item) = match (&declaration.step_key, &declaration.item) {
+            (Some(step_key), Ok(item)) => (step_key, item),
+            _ => continue,
+        };
+        if !metadata.sections.contains_key(step_key) {
+            let line = header_line(step_key);
+            let index = metadata
+                .sections
+                .keys()
+                .position(|key| header_line(key).is_some_and(|other| Some(other) > line))
+                .unwrap_or(metadata.sections.len());
+            metadata.sections.shift_insert(
+                index,
+                step_key.clone(),
+                SectionMetadata {
+                    input: None,
+                    output: None,
+                },
+            );
+        }
+        let input = metadata.sections[step_key]
+            .input
+            .get_or_insert_with(InputMetadata::default);
+
+        let part = || PartReference {
+            name: item.name.clone(),
+            quantity: item.quantity,
+            exclude_from_bom: false,
+            exclude_from_overview: false,
+        };
+        match item.category {
+            "hardware" => input.hardware.get_or_insert_with(Vec::new).push(part()),
+            "electronics" => input.electronics.get_or_insert_with(Vec::new).push(part()),
+            "custom_parts" => input.custom_parts.get_or_insert_with(Vec::new).push(part()),
+            "consumables" => {
+                input
+                    .consumables
+                    .get_or_insert_with(Vec::new)
+                    .push(ConsumableReference {
+                        name: item.name.clone(),
+                        exclude_from_bom: false,
+                        exclude_from_overview: false,
+                    })
+            }
+            "tools" => input
+                .tools
+                .get_or_insert_with(Vec::new)
+                .push(ToolReference {
+                    name: item.name.clone(),
+                    setting: item.setting.clone().map(ToolSetting::Text),
+                    exclude_from_bom: false,
+                    exclude_from_overview: false,
+                }),
+            "assemblies" => input
+                .assemblies
+                .get_or_insert_with(Vec::new)
+                .push(AssemblyReference {
+                    name: item.name.clone(),
+                    quantity: item.quantity,
+                    exclude_from_bom: false,
+                    exclude_from_overview: false,
+                }),
+            "subassemblies" => {
+                input
+                    .subassemblies
+                    .get_or_insert_with(Vec::new)
+                    .push(SubassemblyReference {
+                        name: item.name.clone(),
+                        quantity: item.quantity,
+                        exclude_from_bom: false,
+                        exclude_from_overview: false,
+                    })
             }
+            "units" => input
+                .units
+                .get_or_insert_with(Vec::new)
+                .push(UnitReference {
+                    name: item.name.clone(),
+                    quantity: item.quantity,
+                    exclude_from_bom: false,
+                    exclude_from_overview: false,
+                }),
+            _ => {}
+        }
+    }
+}
 
-            result.push("".to_string()); // Empty line
-            result.push("---".to_string()); // Horizontal rule above step
-            result.push("".to_string()); // Empty line
+// Turns valid declarations into `{{#bom-part}}` references and drops lines that
+// only hold declarations. Invalid declarations and those in code are left as written.
+fn replace_inline_declarations(content: &str) -> String {
+    let re = inline_declaration_re();
+    if !re.is_match(content) {
+        return content.to_string();
+    }
+    let code = code_ranges(content);
+
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        // `lines` yields subslices, so this is the line's byte offset in `content`
+        let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+        let outside_code = |offset: usize| !in_code(&code, line_start + offset);
+        if re.is_match(line)
+            && re.find_iter(line).all(|m| outside_code(m.start()))
+            && re.replace_all(line, "").trim().is_empty()
+            && !line.contains("\\{{#")
+        {
+            continue;
         }
+        lines.push(
+            re.replace_all(line, |caps: &regex::Captures| {
+                if !outside_code(caps.get(0).unwrap().start()) {
+                    return caps[0].to_string();
+                }
+                if &caps[1] == "\\" {
+                    return caps[0][1..].to_string();
+                }
+                match parse_inline_declaration(&caps[2], &caps[3]) {
+                    Ok(item) => format!("{{{{#bom-part {}}}}}", item.name),
+                    Err(_) => caps[0].to_string(),
+                }
+            })
+            .into_owned(),
+        );
+    }
 
-        result.push(line.to_string());
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+// `{{#bom-part NAME}}`, optionally escaped with a leading backslash
+const PART_REFERENCE_PATTERN: &str = r"(\\?)\{\{#bom-part\s+([^\s}]+)\s*\}\}";
+static PART_REFERENCE_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+fn part_reference_re() -> &'static regex::Regex {
+    PART_REFERENCE_RE.get_or_init(|| regex::Regex::new(PART_REFERENCE_PATTERN).unwrap())
+}
+
+const CATALOG_PATH: &str = "bom-catalog.md";
 
-        // Check if this line is a step header we need to insert tables after
-        for (step_key, header_line_idx) in &step_headers {
-            if line_idx == *header_line_idx {
-                if let Some(section_metadata) = sections.get(step_key) {
-                    let empty_input = InputMetadata {
-                        hardware: None,
-                        electronics: None,
-                        custom_parts: None,
-                        consumables: None,
-                        tools: None,
-                        assemblies: None,
-                        subassemblies: None,
-                        units: None,
+fn catalog_anchor(category: &str, name: &str) -> String {
+    row_anchor("catalog", category, name)
+}
+
+// Where an inventory item is used
+struct CatalogUse {
+    chapter: String,
+    path: Option<std::path::PathBuf>,
+    step: String,
+    anchor: String,
+    role: &'static str,
+    quantity: Option<u32>,
+}
+
+#[derive(Default)]
+struct PartsCatalog {
+    // Keyed by (category, name); inventory record and uses
+    items: BTreeMap<(&'static str, String), (serde_json::Value, Vec<CatalogUse>)>,
+}
+
+impl PartsCatalog {
+    fn record(
+        &mut self,
+        chapter: &str,
+        path: Option<&std::path::Path>,
+        metadata: &ChapterMetadata,
+        inventory: &Inventory,
+    ) {
+        for (step, section) in &metadata.sections {
+            let inputs = section
+                .input
+                .as_ref()
+                .map(|input| export_input_items(input, inventory))
+                .unwrap_or_default();
+            let outputs = section
+                .output
+                .as_ref()
+                .map(|output| export_output_items(output, inventory))
+                .unwrap_or_default();
+
+            for (role, items) in [("input", inputs), ("output", outputs)] {
+                for item in items {
+                    // Items missing from the inventory have no record to show
+                    let record = match item.inventory {
+                        Some(record) => record,
+                        None => continue,
                     };
-                    let input = section_metadata.input.as_ref().unwrap_or(&empty_input);
-                    let hardware = input.hardware.as_deref().unwrap_or_default();
-                    let electronics = input.electronics.as_deref().unwrap_or_default();
-                    let custom_parts = input.custom_parts.as_deref().unwrap_or_default();
-                    let consumables = input.consumables.as_deref().unwrap_or_default();
-                    let tools = input.tools.as_deref().unwrap_or_default();
-                    let assemblies = input.assemblies.as_deref().unwrap_or_default();
-                    let subassemblies = input.subassemblies.as_deref().unwrap_or_default();
-                    let units = input.units.as_deref().unwrap_or_default();
-
-                    let hardware_table = generate_fasteners_table(hardware, inventory, step_key);
-                    let electronics_table =
-                        generate_electronics_table(electronics, inventory, step_key);
-                    let custom_parts_table =
-                        generate_custom_parts_table(custom_parts, inventory, step_key);
-                    let consumables_table =
-                        generate_consumables_table(consumables, inventory, step_key);
-                    let tools_table = generate_tools_table(tools, inventory, step_key);
-                    let assemblies_table =
-                        generate_assemblies_table(assemblies, inventory, step_key);
-                    let subassemblies_table =
-                        generate_subassemblies_table(subassemblies, inventory, step_key);
-                    let units_table =
-                        generate_units_table(units, inventory, step_key);
-                    let output_table = generate_output_table(
-                        section_metadata.output.as_ref(),
-                        inventory,
-                        step_key,
-                    );
-
-                    let has_input_tables = !hardware_table.is_empty()
-                        || !electronics_table.is_empty()
-                        || !custom_parts_table.is_empty()
-                        || !consumables_table.is_empty()
-                        || !tools_table.is_empty()
-                        || !assemblies_table.is_empty()
-                        || !subassemblies_table.is_empty()
-                        || !units_table.is_empty();
-
-                    if has_input_tables {
-                        // Add Show All button before tables
-                        result.push("".to_string()); // Empty line
-                        result.push(generate_show_all_button(step_key));
+                    let id_prefix = match role {
+                        "output" => format!("output_{}", item.category),
+                        _ => item.category.to_string(),
+                    };
+                    self.items
+                        .entry((item.category, item.name.clone()))
+                        .or_insert_with(|| (record, Vec::new()))
+                        .1
+                        .push(CatalogUse {
+                            chapter: chapter.to_string(),
+                            path: path.map(|p| p.to_path_buf()),
+                            step: step.clone(),
+                            anchor: row_anchor(&id_prefix, step, &item.name),
+                            role,
+                            quantity: item.quantity,
+                        });
+                }
+            }
+        }
+    }
 
-                        result.push("".to_string());
-                        result.push(generate_labeled_divider("Input"));
-                    }
+    fn render(&self, title: &str, styles: &BTreeMap<String, CategoryStyle>) -> String {
+        let mut content = format!("# {}\n\n", title);
+        if self.items.is_empty() {
+            content.push_str("No inventory items are used in this book.\n");
+            return content;
+        }
 
-                    if !hardware_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(hardware_table.lines().map(|s| s.to_string()));
-                    }
-                    if !electronics_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(electronics_table.lines().map(|s| s.to_string()));
-                    }
-                    if !custom_parts_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(custom_parts_table.lines().map(|s| s.to_string()));
-                    }
-                    if !subassemblies_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(subassemblies_table.lines().map(|s| s.to_string()));
-                    }
-                    if !assemblies_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(assemblies_table.lines().map(|s| s.to_string()));
-                    }
-                    if !units_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(units_table.lines().map(|s| s.to_string()));
-                    }
-                    if !tools_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(tools_table.lines().map(|s| s.to_string()));
-                    }
-                    if !consumables_table.is_empty() {
-                        result.push("".to_string());
-                        result.extend(consumables_table.lines().map(|s| s.to_string()));
-                    }
+        for category in BOM_CATEGORIES {
+            let items: Vec<_> = self
+                .items
+                .iter()
+                .filter(|((item_category, _), _)| *item_category == category)
+                .collect();
+            if items.is_empty() {
+                continue;
+            }
+            content.push_str(&format!("## {}\n\n", category_title(category, styles)));
 
-                    if has_input_tables {
-                        result.push("".to_string()); // Empty line after input tables
-                    }
+            for ((_, name), (record, uses)) in items {
+                content.push_str(&format!(
+                    "### {} {{#{}}}\n\n",
+                    name,
+                    catalog_anchor(category, name)
+                ));
 
-                    // Defer output table to end of step content
-                    if !output_table.is_empty() {
-                        pending_output = Some(output_table);
+                // Full inventory record; the name is already the heading
+                if let Some(fields) = record.as_object() {
+                    let rows: Vec<String> = fields
+                        .iter()
+                        .filter(|(field, _)| *field != "name")
+                        .map(|(field, value)| {
+                            let value = match value {
+                                // The catalog lives at the root of `src`, where image paths start
+                                serde_json::Value::String(image) if field == "image" => {
+                                    Cell::Image {
+                                        src: image.trim_start_matches("./").to_string(),
+                                        alt: name.clone(),
+                                    }
+                                }
+                                serde_json::Value::String(href)
+                                    if field == "datasheet" || field == "url" =>
+                                {
+                                    Cell::Link {
+                                        text: href.clone(),
+                                        href: href.trim_start_matches("./").replace(' ', "%20"),
+                                    }
+                                }
+                                serde_json::Value::Null => Cell::NoDescription,
+                                // Stock levels are read as floats; show whole numbers without `.0`
+                                serde_json::Value::Number(number) => Cell::Text(
+                                    number
+                                        .as_f64()
+                                        .map_or_else(|| number.to_string(), |n| n.to_string()),
+                                ),
+                                serde_json::Value::String(text) => Cell::Text(text.clone()),
+                                other => Cell::Text(other.to_string()),
+                            };
+                            format!("| {} | {} |\n", field_label(field), value.to_markdown())
+                        })
+                        .collect();
+                    if !rows.is_empty() {
+                        content.push_str("| Field | Value |\n| --- | --- |\n");
+                        content.push_str(&rows.concat());
+                        content.push('\n');
                     }
                 }
-                break;
+
+                content.push_str("Used in:\n\n");
+                for catalog_use in uses {
+                    let link = match &catalog_use.path {
+                        Some(path) => format!(
+                            "[{} › {}]({}#{})",
+                            catalog_use.chapter,
+                            catalog_use.step,
+                            path.to_string_lossy().replace('\\', "/"),
+                            catalog_use.anchor
+                        ),
+                        None => format!("{} › {}", catalog_use.chapter, catalog_use.step),
+                    };
+                    let quantity = catalog_use
+                        .quantity
+                        .map(|q| format!(" ×{}", q))
+                        .unwrap_or_default();
+                    let role = if catalog_use.role == "output" {
+                        " (output)"
+                    } else {
+                        ""
+                    };
+                    content.push_str(&format!("- {}{}{}\n", link, quantity, role));
+                }
+                content.push('\n');
             }
         }
+
+        content
     }
+}
 
-    // Flush any remaining pending output table at end of content
-    if let Some(output_table) = pending_output.take() {
-        result.push("".to_string());
-        result.extend(output_table.lines().map(|s| s.to_string()));
-        result.push("".to_string());
+// Table and catalog heading, with the icon from `[preprocessor.bom.categories]` if set
+fn category_title(category: &str, styles: &BTreeMap<String, CategoryStyle>) -> String {
+    let icon = styles
+        .get(category)
+        .and_then(|style| style.icon.as_deref())
+        .unwrap_or_else(|| default_category_icon(category));
+    let name = match category {
+        "hardware" => "Hardware",
+        "electronics" => "Electronics",
+        "custom_parts" => "Custom Parts",
+        "consumables" => "Consumables",
+        "tools" => "Tools",
+        "assemblies" => "Assemblies",
+        "subassemblies" => "Subassemblies",
+        "units" => "Units",
+        _ => return "Other".to_string(),
+    };
+    if icon.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", icon, name)
     }
+}
 
-    result.join("\n")
+fn default_category_icon(category: &str) -> &'static str {
+    match category {
+        "hardware" => "🔩",
+        "electronics" => "🔌",
+        "custom_parts" => "⚙️",
+        "consumables" => "🧪",
+        "tools" => "🔧",
+        "assemblies" => "\u{1f4e6}",
+        "subassemblies" => "\u{1f9e9}",
+        "units" => "\u{2b50}",
+        _ => "",
+    }
 }
 
-fn generate_overview_tables(
-    sections: &std::collections::HashMap<String, SectionMetadata>,
+// `custom_parts` -> `bom-custom-parts`
+fn category_class(category: &str) -> String {
+    format!("bom-{}", category.replace('_', "-"))
+}
+
+// `unit_cost` -> `Unit cost`
+fn field_label(field: &str) -> String {
+    if field == "url" {
+        return "URL".to_string();
+    }
+    let label = field.replace('_', " ");
+    let mut chars = label.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => label,
+    }
+}
+
+fn row_anchor(id_prefix: &str, section_id: &str, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}-{}-{}", id_prefix, section_id, name)
+}
+
+fn escape_html_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Id prefix of the table in `section` that lists `name`, if any
+fn table_listing(
+    section: &SectionMetadata,
+    name: &str,
     inventory: &Inventory,
+    overview: bool,
+) -> Option<String> {
+    let inputs = section
+        .input
+        .as_ref()
+        .map(|input| export_input_items(input, inventory))
+        .unwrap_or_default();
+    let outputs = section
+        .output
+        .as_ref()
+        .map(|output| export_output_items(output, inventory))
+        .unwrap_or_default();
+
+    let listed =
+        |item: &&ItemExport| item.name == name && !(overview && item.exclude_from_overview);
+    inputs
+        .iter()
+        .find(listed)
+        .map(|item| item.category.to_string())
+        .or_else(|| {
+            outputs
+                .iter()
+                .find(listed)
+                .map(|item| format!("output_{}", item.category))
+        })
+}
+
+// Expands `{{#bom-part NAME}}` into the part name with its description as a
+// tooltip, linked to the part's row in the enclosing step's tables, else in the
+// overview, else in the first step listing it. `\{{#bom-part ...}}` and references
+// in code blocks and code spans are kept as written.
+fn expand_part_references(
+    content: &str,
+    sections: &IndexMap<String, SectionMetadata>,
+    inventory: &Inventory,
+    matcher: &StepMatcher,
+    mode: RenderMode,
 ) -> String {
-    // Aggregate all parts from all sections
-    let mut all_hardware = Vec::new();
-    let mut all_electronics = Vec::new();
-    let mut all_custom_parts = Vec::new();
-    let mut all_consumables = Vec::new();
-    let mut all_tools = Vec::new();
-    let mut all_assemblies = Vec::new();
-    let mut all_subassemblies = Vec::new();
-    let mut all_units = Vec::new();
-    let mut all_outputs = Vec::new();
+    let re = part_reference_re();
+    if !re.is_match(content) {
+        return content.to_string();
+    }
 
-    for section_metadata in sections.values() {
-        if let Some(input) = &section_metadata.input {
-            if let Some(hardware) = &input.hardware {
-                all_hardware.extend(hardware.clone());
+    let code = code_ranges(content);
+    let step_headers = find_step_headers(content, sections, matcher);
+    let all_sections: Vec<&SectionMetadata> = sections.values().collect();
+    let overview = merge_sections(&all_sections);
+
+    re.replace_all(content, |caps: &regex::Captures| {
+        if in_code(&code, caps.get(0).unwrap().start()) {
+            return caps[0].to_string();
+        }
+        if &caps[1] == "\\" {
+            return caps[0][1..].to_string();
+        }
+        let name = &caps[2];
+        let line_idx = content[..caps.get(0).unwrap().start()]
+            .matches('\n')
+            .count();
+
+        // Innermost step header above the reference
+        let enclosing_step = step_headers.iter().rposition(|h| h.line_idx <= line_idx);
+        let anchor = enclosing_step
+            .and_then(|idx| {
+                let section = step_section_metadata(&step_headers, idx, sections)?;
+                let id_prefix = table_listing(&section, name, inventory, false)?;
+                Some(row_anchor(&id_prefix, &step_headers[idx].key, name))
+            })
+            .or_else(|| {
+                let id_prefix = table_listing(&overview, name, inventory, true)?;
+                Some(row_anchor(&id_prefix, "overview", name))
+            })
+            .or_else(|| {
+                step_headers.iter().find_map(|h| {
+                    let id_prefix = table_listing(sections.get(&h.key)?, name, inventory, false)?;
+                    Some(row_anchor(&id_prefix, &h.key, name))
+                })
+            });
+
+        let category = match inventory.categories_of(name).first() {
+            Some(category) => *category,
+            None => {
+                warn!("Part reference '{}' not found in inventory", name);
+                return match mode {
+                    RenderMode::Html => format!(
+                        "<span class=\"bom-not-found\" title=\"Not found in inventory\">{}</span>",
+                        escape_html_attribute(name)
+                    ),
+                    _ => format!("**{}** ⚠️", name),
+                };
             }
-            if let Some(electronics) = &input.electronics {
-                all_electronics.extend(electronics.clone());
+        };
+        let description = inventory.describe(category, name);
+
+        match mode {
+            RenderMode::Html => {
+                let abbr = format!(
+                    "<abbr title=\"{}\">{}</abbr>",
+                    escape_html_attribute(
+                        description.as_deref().unwrap_or("No description provided")
+                    ),
+                    escape_html_attribute(name)
+                );
+                match anchor {
+                    // mdbook-bom.js opens the collapsed table before jumping to the row
+                    Some(anchor) => format!(
+                        "<a class=\"bom-part\" href=\"#{}\">{}</a>",
+                        escape_html_attribute(&anchor),
+                        abbr
+                    ),
+                    None => abbr,
+                }
             }
-            if let Some(custom_parts) = &input.custom_parts {
-                all_custom_parts.extend(custom_parts.clone());
+            _ => match description {
+                Some(description) => format!("**{}** ({})", name, description),
+                None => format!("**{}**", name),
+            },
+        }
+    })
+    .into_owned()
+}
+
+fn insert_section_tables(
+    content: &str,
+    sections: &IndexMap<String, SectionMetadata>,
+    inventory: &Inventory,
+    matcher: &StepMatcher,
+    options: &TableOptions,
+) -> String {
+    let mode = options.mode;
+    let step_headers = find_step_headers(content, sections, matcher);
+    let lines: Vec<&str> = content.lines().collect();
+    let mut result = Vec::new();
+    // Output tables wait for the end of their step, keyed by header level
+    let mut pending_outputs: Vec<(usize, String)> = Vec::new();
+
+    // Generate overview tables (without header)
+    let overview_section = generate_overview_tables(sections, inventory, options);
+
+    // The overview goes after the chapter's first top-level header
+    let overview_line = find_headings(content)
+        .iter()
+        .find(|heading| heading.level == 1)
+        .map(|heading| heading.end_line_idx);
+
+    for (line_idx, line) in lines.iter().enumerate() {
+        let header_idx = step_headers.iter().position(|h| h.line_idx == line_idx);
+
+        if let Some(header_idx) = header_idx {
+            let header = &step_headers[header_idx];
+
+            // Flush pending output tables of this step's finished siblings and their sub-steps
+            while pending_outputs
+                .last()
+                .is_some_and(|(level, _)| *level >= header.level)
+            {
+                let (_, output_table) = pending_outputs.pop().unwrap();
+                result.push("".to_string());
+                result.extend(output_table.lines().map(|s| s.to_string()));
+                result.push("".to_string());
+            }
+
+            // Add horizontal rule before top-level steps
+            if !header.is_sub_step && line_idx > 0 {
+                result.push("".to_string()); // Empty line
+                result.push("---".to_string()); // Horizontal rule above step
+                result.push("".to_string()); // Empty line
+            }
+        }
+
+        result.push(line.to_string());
+
+        if overview_line == Some(line_idx) {
+            // Insert overview tables after the top-level header
+            if !overview_section.trim().is_empty() {
+                result.push("".to_string()); // Empty line
+                result.extend(overview_section.lines().map(|s| s.to_string()));
+                result.push("".to_string()); // Empty line after overview
+            }
+            continue;
+        }
+
+        // Check if this line ends a step header we need to insert tables after
+        let header_idx = step_headers.iter().position(|h| h.end_line_idx == line_idx);
+        if let Some(header_idx) = header_idx {
+            let header = &step_headers[header_idx];
+            let step_key = &header.key;
+
+            if let Some(section_metadata) =
+                step_section_metadata(&step_headers, header_idx, sections)
+            {
+                let section_metadata = &section_metadata;
+                let empty_input = InputMetadata {
+                    hardware: None,
+                    electronics: None,
+                    custom_parts: None,
+                    consumables: None,
+                    tools: None,
+                    assemblies: None,
+                    subassemblies: None,
+                    units: None,
+                };
+                let input = section_metadata.input.as_ref().unwrap_or(&empty_input);
+                let hardware = input.hardware.as_deref().unwrap_or_default();
+                let electronics = input.electronics.as_deref().unwrap_or_default();
+                let custom_parts = input.custom_parts.as_deref().unwrap_or_default();
+                let consumables = input.consumables.as_deref().unwrap_or_default();
+                let tools = input.tools.as_deref().unwrap_or_default();
+                let assemblies = input.assemblies.as_deref().unwrap_or_default();
+                let subassemblies = input.subassemblies.as_deref().unwrap_or_default();
+                let units = input.units.as_deref().unwrap_or_default();
+
+                let hardware_table =
+                    generate_fasteners_table(hardware, inventory, step_key, options);
+                let electronics_table =
+                    generate_electronics_table(electronics, inventory, step_key, options);
+                let custom_parts_table =
+                    generate_custom_parts_table(custom_parts, inventory, step_key, options);
+                let consumables_table =
+                    generate_consumables_table(consumables, inventory, step_key, options);
+                let tools_table =
+                    generate_tools_table(&deduplicate_tools(tools), inventory, step_key, options);
+                let assemblies_table =
+                    generate_assemblies_table(assemblies, inventory, step_key, options);
+                let subassemblies_table =
+                    generate_subassemblies_table(subassemblies, inventory, step_key, options);
+                let units_table = generate_units_table(units, inventory, step_key, options);
+                let output_table = generate_output_table(
+                    section_metadata.output.as_ref(),
+                    inventory,
+                    step_key,
+                    options,
+                );
+
+                let has_input_tables = !hardware_table.is_empty()
+                    || !electronics_table.is_empty()
+                    || !custom_parts_table.is_empty()
+                    || !consumables_table.is_empty()
+                    || !tools_table.is_empty()
+                    || !assemblies_table.is_empty()
+                    || !subassemblies_table.is_empty()
+                    || !units_table.is_empty();
+
+                if has_input_tables {
+                    // Add Show All button before tables
+                    if options.interactive {
+                        result.push("".to_string()); // Empty line
+                        result.push(generate_show_all_button(step_key));
+                        if let Some(version) = options.checklist {
+                            result.push(generate_checklist_progress(step_key, version));
+                        }
+                    }
+
+                    result.push("".to_string());
+                    result.push(generate_labeled_divider("Input", mode));
+                }
+
+                if !hardware_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(hardware_table.lines().map(|s| s.to_string()));
+                }
+                if !electronics_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(electronics_table.lines().map(|s| s.to_string()));
+                }
+                if !custom_parts_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(custom_parts_table.lines().map(|s| s.to_string()));
+                }
+                if !subassemblies_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(subassemblies_table.lines().map(|s| s.to_string()));
+                }
+                if !assemblies_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(assemblies_table.lines().map(|s| s.to_string()));
+                }
+                if !units_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(units_table.lines().map(|s| s.to_string()));
+                }
+                if !tools_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(tools_table.lines().map(|s| s.to_string()));
+                }
+                if !consumables_table.is_empty() {
+                    result.push("".to_string());
+                    result.extend(consumables_table.lines().map(|s| s.to_string()));
+                }
+
+                if has_input_tables {
+                    result.push("".to_string()); // Empty line after input tables
+                }
+
+                // Defer output table to end of step content
+                if !output_table.is_empty() {
+                    pending_outputs.push((header.level, output_table));
+                }
+            }
+        }
+    }
+
+    // Flush any remaining pending output tables at end of content, innermost first
+    while let Some((_, output_table)) = pending_outputs.pop() {
+        result.push("".to_string());
+        result.extend(output_table.lines().map(|s| s.to_string()));
+        result.push("".to_string());
+    }
+
+    result.join("\n")
+}
+
+fn generate_overview_tables(
+    sections: &IndexMap<String, SectionMetadata>,
+    inventory: &Inventory,
+    options: &TableOptions,
+) -> String {
+    let mode = options.mode;
+    // Aggregate all parts from all sections
+    let mut all_hardware = Vec::new();
+    let mut all_electronics = Vec::new();
+    let mut all_custom_parts = Vec::new();
+    let mut all_consumables = Vec::new();
+    let mut all_tools = Vec::new();
+    let mut all_assemblies = Vec::new();
+    let mut all_subassemblies = Vec::new();
+    let mut all_units = Vec::new();
+    let mut all_outputs = Vec::new();
+
+    for section_metadata in sections.values() {
+        if let Some(input) = &section_metadata.input {
+            if let Some(hardware) = &input.hardware {
+                all_hardware.extend(hardware.clone());
+            }
+            if let Some(electronics) = &input.electronics {
+                all_electronics.extend(electronics.clone());
+            }
+            if let Some(custom_parts) = &input.custom_parts {
+                all_custom_parts.extend(custom_parts.clone());
             }
             if let Some(consumables) = &input.consumables {
                 all_consumables.extend(consumables.clone());
@@ -995,20 +3362,22 @@ fn generate_overview_tables(
     let mut overview = String::new();
 
     // Generate overview tables
-    let hardware_table = generate_fasteners_table(&combined_hardware, inventory, "overview");
+    let hardware_table =
+        generate_fasteners_table(&combined_hardware, inventory, "overview", options);
     let electronics_table =
-        generate_electronics_table(&combined_electronics, inventory, "overview");
+        generate_electronics_table(&combined_electronics, inventory, "overview", options);
     let custom_parts_table =
-        generate_custom_parts_table(&combined_custom_parts, inventory, "overview");
+        generate_custom_parts_table(&combined_custom_parts, inventory, "overview", options);
     let consumables_table =
-        generate_consumables_table(&combined_consumables, inventory, "overview");
-    let tools_table = generate_tools_table(&combined_tools, inventory, "overview");
+        generate_consumables_table(&combined_consumables, inventory, "overview", options);
+    let tools_table = generate_tools_table(&combined_tools, inventory, "overview", options);
     let assemblies_table =
-        generate_assemblies_table(&combined_assemblies, inventory, "overview");
+        generate_assemblies_table(&combined_assemblies, inventory, "overview", options);
     let subassemblies_table =
-        generate_subassemblies_table(&combined_subassemblies, inventory, "overview");
-    let units_table = generate_units_table(&combined_units, inventory, "overview");
-    let output_table = generate_output_table(Some(&filtered_output), inventory, "overview");
+        generate_subassemblies_table(&combined_subassemblies, inventory, "overview", options);
+    let units_table = generate_units_table(&combined_units, inventory, "overview", options);
+    let output_table =
+        generate_output_table(Some(&filtered_output), inventory, "overview", options);
 
     let has_input_tables = !hardware_table.is_empty()
         || !electronics_table.is_empty()
@@ -1022,11 +3391,14 @@ fn generate_overview_tables(
     let has_tables = has_input_tables || !output_table.is_empty();
 
     if has_tables {
-        overview.push_str(&generate_show_all_button("overview"));
-        overview.push('\n');
+        if options.interactive {
+            overview.push_str(&generate_show_all_button("overview"));
+            overview.push_str(&generate_build_quantity_input());
+            overview.push('\n');
+        }
 
         if has_input_tables {
-            overview.push_str(&generate_labeled_divider("Input"));
+            overview.push_str(&generate_labeled_divider("Input", mode));
             overview.push('\n');
         }
 
@@ -1072,8 +3444,7 @@ fn generate_overview_tables(
 }
 
 fn combine_parts(parts: &[PartReference]) -> Vec<PartReference> {
-    let mut combined: std::collections::HashMap<String, (u32, bool, bool)> =
-        std::collections::HashMap::new();
+    let mut combined: BTreeMap<String, (u32, bool, bool)> = BTreeMap::new();
 
     for part in parts {
         combined
@@ -1098,8 +3469,7 @@ fn combine_parts(parts: &[PartReference]) -> Vec<PartReference> {
 }
 
 fn deduplicate_consumables(consumables: &[ConsumableReference]) -> Vec<ConsumableReference> {
-    let mut combined: std::collections::HashMap<String, (bool, bool)> =
-        std::collections::HashMap::new();
+    let mut combined: BTreeMap<String, (bool, bool)> = BTreeMap::new();
 
     for consumable in consumables {
         combined
@@ -1121,104 +3491,566 @@ fn deduplicate_consumables(consumables: &[ConsumableReference]) -> Vec<Consumabl
         .collect()
 }
 
-fn deduplicate_tools(tools: &[ToolReference]) -> Vec<ToolReference> {
-    let mut combined: std::collections::HashMap<
-        String,
-        (std::collections::HashSet<String>, bool, bool),
-    > = std::collections::HashMap::new();
+// A tool as listed in the tables, with the distinct settings of every listing
+#[derive(Debug, Clone)]
+struct CombinedTool {
+    name: String,
+    settings: Vec<ToolSetting>,
+    exclude_from_overview: bool,
+}
+
+// One entry per tool name, sorted by name
+fn deduplicate_tools(tools: &[ToolReference]) -> Vec<CombinedTool> {
+    // Settings are kept in the order they first appear
+    let mut combined: BTreeMap<String, (Vec<ToolSetting>, bool)> = BTreeMap::new();
 
     for tool in tools {
         let entry = combined
             .entry(tool.name.clone())
-            .or_insert_with(|| (std::collections::HashSet::new(), tool.exclude_from_bom, tool.exclude_from_overview));
+            .or_insert_with(|| (Vec::new(), tool.exclude_from_overview));
         if let Some(setting) = &tool.setting {
-            entry.0.insert(setting.clone());
+            let setting = setting.normalized();
+            if !entry.0.contains(&setting) {
+                entry.0.push(setting);
+            }
         }
-        entry.1 = entry.1 && tool.exclude_from_bom;
-        entry.2 = entry.2 && tool.exclude_from_overview;
+        entry.1 = entry.1 && tool.exclude_from_overview;
     }
 
     combined
         .into_iter()
-        .map(|(name, (settings, exclude_from_bom, exclude_from_overview))| {
-            let setting = if settings.is_empty() {
-                None
-            } else {
-                Some(settings.into_iter().collect::<Vec<_>>().join(", "))
-            };
-            ToolReference {
-                name,
-                setting,
-                exclude_from_bom,
-                exclude_from_overview,
-            }
+        .map(|(name, (settings, exclude_from_overview))| CombinedTool {
+            name,
+            settings,
+            exclude_from_overview,
         })
         .collect()
 }
 
+// Toggles every table of the step; the handler lives in the shared mdbook-bom.js
 fn generate_show_all_button(section_id: &str) -> String {
     format!(
-        r#"
-<button onclick="toggleAllTables('{}')" class="bom-show-all-button" style="
-    background: transparent;
-    color: var(--icons, #747474);
-    border: 1px solid var(--icons, #747474);
-    padding: 8px 16px;
-    border-radius: 4px;
-    cursor: pointer;
-    font-size: 14px;
-    margin-bottom: 10px;
-    transition: all 0.2s ease;
-" onmouseover="
-    this.style.color='var(--icons-hover, #000000)';
-    this.style.borderColor='var(--icons-hover, #000000)';
-    this.style.backgroundColor='var(--theme-hover, #e6e6e6)';
-" onmouseout="
-    this.style.color='var(--icons, #747474)';
-    this.style.borderColor='var(--icons, #747474)';
-    this.style.backgroundColor='transparent';
-">
-    Show All
-</button>
-
-<script>
-function toggleAllTables(sectionId) {{
-    const button = event.target;
-    const isShowing = button.textContent === 'Hide All';
-    const newState = !isShowing;
-    const newText = newState ? 'Hide All' : 'Show All';
-
-    button.textContent = newText;
-
-    // Find all details elements for this section
-    const detailsElements = [
-        document.getElementById('hardware-' + sectionId),
-        document.getElementById('electronics-' + sectionId),
-        document.getElementById('custom_parts-' + sectionId),
-        document.getElementById('consumables-' + sectionId),
-        document.getElementById('tools-' + sectionId),
-        document.getElementById('assemblies-' + sectionId),
-        document.getElementById('subassemblies-' + sectionId),
-        document.getElementById('units-' + sectionId),
-        document.getElementById('output_custom_parts-' + sectionId),
-        document.getElementById('output_assemblies-' + sectionId),
-        document.getElementById('output_subassemblies-' + sectionId),
-        document.getElementById('output_units-' + sectionId)
-    ].filter(el => el !== null);
-
-    detailsElements.forEach(details => {{
-        details.open = newState;
-    }});
-}}
-</script>"#,
-        section_id
+        "\n<button type=\"button\" class=\"bom-show-all-button\" data-bom-section=\"{}\" aria-expanded=\"false\">Show All</button>",
+        escape_html_attribute(section_id)
+    )
+}
+
+// Number of units to build; mdbook-bom.js multiplies every quantity on the page by it
+fn generate_build_quantity_input() -> String {
+    " <label class=\"bom-build-quantity\">Units to build <input type=\"number\" class=\"bom-build-quantity-input\" min=\"1\" step=\"1\" value=\"1\" inputmode=\"numeric\"></label>".to_string()
+}
+
+// Filled in by mdbook-bom.js from the ticked checkboxes of the step
+fn generate_checklist_progress(section_id: &str, version: &str) -> String {
+    format!(
+        "<span class=\"bom-progress\" data-bom-section=\"{0}\" data-bom-checklist=\"{1}\" aria-live=\"polite\"></span> <button type=\"button\" class=\"bom-checklist-clear\" data-bom-section=\"{0}\">Clear</button>",
+        escape_html_attribute(section_id),
+        escape_html_attribute(version)
     )
 }
 
+// Rendering settings shared by all tables of a chapter
+struct TableOptions<'a> {
+    mode: RenderMode,
+    // Relative path from the chapter to the root of the book's `src`, e.g. `../`
+    path_to_root: String,
+    // Link names to the generated parts catalog
+    catalog: bool,
+    // Book-wide shortages, marked in the overview tables
+    shortages: &'a [Shortage],
+    // Icon overrides per category
+    categories: &'a BTreeMap<String, CategoryStyle>,
+    // Whether mdbook-bom.js is loaded, which the Show All buttons, the checklist and
+    // the build quantity input need
+    interactive: bool,
+    // Book version the checklist ticks are saved under; `None` without a checklist
+    checklist: Option<&'a str>,
+    // Units torque settings are also shown in
+    torque_units: &'a [String],
+}
+
+impl<'a> TableOptions<'a> {
+    fn for_chapter(
+        mode: RenderMode,
+        chapter_path: Option<&std::path::Path>,
+        config: &'a BomConfig,
+        shortages: &'a [Shortage],
+        checklist: Option<&'a str>,
+    ) -> Self {
+        let depth = chapter_path
+            .and_then(|path| path.parent())
+            .map_or(0, |parent| parent.components().count());
+        // Markdown renderers get the checklist as a column to tick on paper
+        let interactive = mode == RenderMode::Html && config.script_installed;
+        TableOptions {
+            mode,
+            path_to_root: "../".repeat(depth),
+            catalog: config.catalog,
+            shortages,
+            torque_units: &config.torque_units,
+            categories: &config.categories,
+            interactive,
+            checklist: checklist.filter(|_| mode != RenderMode::Html || interactive),
+        }
+    }
+
+    fn category_title(&self, category: &str) -> String {
+        category_title(category, self.categories)
+    }
+
+    fn shortage(&self, category: &str, name: &str) -> Option<&'a Shortage> {
+        self.shortages
+            .iter()
+            .find(|shortage| shortage.category == category && shortage.name == name)
+    }
+
+    // Name cell, linked to the item's catalog entry when there is one
+    fn name_cell(&self, category: &str, name: &str, inventory: &Inventory) -> Cell {
+        if self.catalog && inventory.contains(category, name) {
+            Cell::Link {
+                text: name.to_string(),
+                href: format!(
+                    "{}{}#{}",
+                    self.path_to_root,
+                    CATALOG_PATH,
+                    catalog_anchor(category, name)
+                ),
+            }
+        } else {
+            Cell::Text(name.to_string())
+        }
+    }
+
+    // Image and datasheet paths in the inventory are relative to `src`; URLs are used as they are
+    fn book_href(&self, path: &str) -> String {
+        if is_url(path) {
+            path.to_string()
+        } else {
+            format!("{}{}", self.path_to_root, path.trim_start_matches("./"))
+        }
+    }
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://") || path.starts_with("data:")
+}
+
+// Local images of the chapter's items that do not exist under the book's `src` directory
+fn collect_missing_images(
+    metadata: &ChapterMetadata,
+    inventory: &Inventory,
+    src_root: &std::path::Path,
+    missing: &mut BTreeSet<String>,
+) {
+    for section in metadata.sections.values() {
+        let mut items = section
+            .input
+            .as_ref()
+            .map(|input| export_input_items(input, inventory))
+            .unwrap_or_default();
+        if let Some(output) = &section.output {
+            items.extend(export_output_items(output, inventory));
+        }
+
+        for item in items {
+            if let Some(image) = inventory.image(item.category, &item.name) {
+                if !is_url(image) && !src_root.join(image).is_file() {
+                    missing.insert(image.to_string());
+                }
+            }
+        }
+    }
+}
+
+// Whether a table lists what a step consumes or what it produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableKind {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone)]
+enum Cell {
+    Text(String),
+    // Inventory entry exists but has no description
+    NoDescription,
+    // Referenced item is missing from the inventory; holds the category label
+    NotFound(&'static str),
+    Link {
+        text: String,
+        href: String,
+    },
+    // Thumbnail that enlarges on click
+    Image {
+        src: String,
+        alt: String,
+    },
+    // External links, opened in a new tab
+    Links(Vec<(&'static str, String)>),
+    // Stock does not cover the whole build: units short, needed book-wide and on hand
+    Short {
+        short: u32,
+        required: u32,
+        on_hand: f64,
+    },
+    // Checklist box for gathering an item; holds the item name
+    Check(String),
+    // Quantity for one unit, scaled by the build quantity calculator
+    Quantity(u32),
+}
+
+impl Cell {
+    fn description(description: Option<&str>) -> Self {
+        match description {
+            Some(d) => Cell::Text(d.to_string()),
+            None => Cell::NoDescription,
+        }
+    }
+
+    fn is_missing(&self) -> bool {
+        matches!(self, Cell::NotFound(_))
+    }
+
+    fn to_html(&self) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::NoDescription => {
+                "<span class=\"bom-no-description\">No description provided</span>".to_string()
+            }
+            Cell::NotFound(label) => format!(
+                "<span class=\"bom-not-found\">{} not found in inventory</span>",
+                label
+            ),
+            Cell::Link { text, href } => format!("<a href=\"{}\">{}</a>", href, text),
+            Cell::Image { src, alt } => format!(
+                "<img class=\"bom-thumbnail\" src=\"{}\" alt=\"{}\" loading=\"lazy\">",
+                escape_html_attribute(src),
+                escape_html_attribute(alt)
+            ),
+            Cell::Links(links) => links
+                .iter()
+                .map(|(label, href)| {
+                    format!(
+                        "<a href=\"{}\" target=\"_blank\" rel=\"noopener\">{}</a>",
+                        escape_html_attribute(href),
+                        label
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" · "),
+            Cell::Short { short, required, on_hand } => format!(
+                "<span class=\"bom-short\">⚠️ {} short ({} needed for the build, {} on hand)</span>",
+                short, required, on_hand
+            ),
+            Cell::Quantity(quantity) => format!(
+                "<span class=\"bom-quantity\" data-bom-quantity=\"{0}\">{0}</span>",
+                quantity
+            ),
+            // Ticks are saved by name; row ids replace punctuation and can collide
+            Cell::Check(name) => format!(
+                "<input type=\"checkbox\" class=\"bom-check\" data-bom-item=\"{0}\" aria-label=\"Gathered {0}\">",
+                escape_html_attribute(name)
+            ),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        match self {
+            // Pipes would split the cell and newlines would end the row
+            Cell::Text(text) => text.replace('|', "\\|").replace('\n', " "),
+            Cell::NoDescription => "*No description provided*".to_string(),
+            Cell::NotFound(label) => format!("⚠️ **{} not found in inventory**", label),
+            Cell::Link { text, href } => format!("[{}]({})", text.replace('|', "\\|"), href),
+            Cell::Image { src, alt } => format!(
+                "![{}]({})",
+                alt.replace('|', "\\|"),
+                src.replace(' ', "%20")
+            ),
+            Cell::Links(links) => links
+                .iter()
+                .map(|(label, href)| format!("[{}]({})", label, href.replace(' ', "%20")))
+                .collect::<Vec<_>>()
+                .join(" · "),
+            Cell::Short {
+                short,
+                required,
+                on_hand,
+            } => format!(
+                "⚠️ **{} short** ({} needed for the build, {} on hand)",
+                short, required, on_hand
+            ),
+            Cell::Check(_) => "☐".to_string(),
+            Cell::Quantity(quantity) => quantity.to_string(),
+        }
+    }
+}
+
+// One collapsible component table, rendered as HTML or as a markdown pipe table
+struct ComponentTable {
+    id_prefix: &'static str,
+    section_id: String,
+    kind: TableKind,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<Cell>>,
+}
+
+impl ComponentTable {
+    fn new(
+        id_prefix: &'static str,
+        section_id: &str,
+        kind: TableKind,
+        headers: &'static [&'static str],
+    ) -> Self {
+        ComponentTable {
+            id_prefix,
+            section_id: section_id.to_string(),
+            kind,
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    fn push_row(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    fn has_missing(&self) -> bool {
+        self.rows.iter().flatten().any(Cell::is_missing)
+    }
+
+    // Output tables share the category of their input counterparts
+    fn category(&self) -> &'static str {
+        self.id_prefix.trim_start_matches("output_")
+    }
+
+    fn render(&self, options: &TableOptions, inventory: &Inventory) -> String {
+        let columns = InventoryColumns::for_table(self, options, inventory);
+        match options.mode {
+            RenderMode::Html => self.to_html(&columns, options),
+            RenderMode::Markdown => self.to_markdown(&columns, options),
+            RenderMode::None => String::new(),
+        }
+    }
+
+    fn to_html(&self, columns: &InventoryColumns, options: &TableOptions) -> String {
+        let kind_class = match self.kind {
+            TableKind::Input => "bom-input",
+            TableKind::Output => "bom-output",
+        };
+        let title_class = if self.has_missing() {
+            " class=\"bom-missing\""
+        } else {
+            ""
+        };
+        let header_cells: String = columns
+            .headers(self.headers)
+            .iter()
+            .map(|h| format!("<th>{}</th>", h))
+            .collect();
+
+        let mut table = format!(
+            "<details id=\"{}-{}\" class=\"bom-table {} {}\" data-bom-section=\"{}\">\n\
+             <summary><strong{}>{}</strong></summary>\n<br>\n\
+             <table class=\"bom-items\">\n<thead>\n<tr>{}</tr>\n</thead>\n<tbody>\n",
+            self.id_prefix,
+            self.section_id,
+            kind_class,
+            category_class(self.category()),
+            escape_html_attribute(&self.section_id),
+            title_class,
+            options.category_title(self.category()),
+            header_cells
+        );
+
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            let cells: String = columns
+                .row(
+                    row_idx,
+                    row.iter().map(Cell::to_html).collect(),
+                    Cell::to_html,
+                )
+                .iter()
+                .map(|c| format!("<td>{}</td>", c))
+                .collect();
+            // Rows are anchored by name so `{{#bom-part}}` references can link to them
+            match row.first() {
+                Some(Cell::Text(name)) | Some(Cell::Link { text: name, .. }) => {
+                    table.push_str(&format!(
+                        "<tr id=\"{}\">{}</tr>\n",
+                        row_anchor(self.id_prefix, &self.section_id, name),
+                        cells
+                    ))
+                }
+                _ => table.push_str(&format!("<tr>{}</tr>\n", cells)),
+            }
+        }
+
+        table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
+        table
+    }
+
+    fn to_markdown(&self, columns: &InventoryColumns, options: &TableOptions) -> String {
+        let warning = if self.has_missing() { " ⚠️" } else { "" };
+        let mut table = format!(
+            "**{}**{}\n\n",
+            options.category_title(self.category()),
+            warning
+        );
+
+        let headers = columns.headers(self.headers);
+        table.push_str(&format!("| {} |\n", headers.join(" | ")));
+        table.push_str(&format!("|{}\n", " --- |".repeat(headers.len())));
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            let cells = columns.row(
+                row_idx,
+                row.iter().map(Cell::to_markdown).collect(),
+                Cell::to_markdown,
+            );
+            table.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+
+        table.push('\n');
+        table
+    }
+}
+
+// Optional columns filled from the inventory rather than the front matter. Each is
+// only shown when at least one row of the table has a value for it.
+struct InventoryColumns {
+    // Gathered checkbox; step input tables only, when the checklist is enabled
+    checks: Option<Vec<Option<Cell>>>,
+    // Thumbnail before the name
+    images: Option<Vec<Option<Cell>>>,
+    // Datasheet and supplier links after the last column
+    links: Option<Vec<Option<Cell>>>,
+    // Shortage for the whole build; overview input tables only
+    stock: Option<Vec<Option<Cell>>>,
+}
+
+impl InventoryColumns {
+    fn for_table(table: &ComponentTable, options: &TableOptions, inventory: &Inventory) -> Self {
+        let category = table.id_prefix.trim_start_matches("output_");
+        let names: Vec<Option<&String>> = table
+            .rows
+            .iter()
+            .map(|row| match row.first() {
+                Some(Cell::Text(name)) | Some(Cell::Link { text: name, .. }) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        let images = names
+            .iter()
+            .map(|name| {
+                let name = (*name)?;
+                inventory.image(category, name).map(|image| Cell::Image {
+                    src: options.book_href(image),
+                    alt: name.clone(),
+                })
+            })
+            .collect();
+        let links = names
+            .iter()
+            .map(|name| {
+                let name = (*name)?;
+                let links: Vec<(&'static str, String)> = [
+                    ("Datasheet", inventory.datasheet(category, name)),
+                    ("Supplier", inventory.supplier_url(category, name)),
+                ]
+                .into_iter()
+                .filter_map(|(label, href)| href.map(|href| (label, options.book_href(href))))
+                .collect();
+                if links.is_empty() {
+                    None
+                } else {
+                    Some(Cell::Links(links))
+                }
+            })
+            .collect();
+
+        let stock = names
+            .iter()
+            .map(|name| {
+                if table.section_id != "overview" || table.kind != TableKind::Input {
+                    return None;
+                }
+                options
+                    .shortage(category, (*name)?)
+                    .map(|shortage| Cell::Short {
+                        short: shortage.short,
+                        required: shortage.required,
+                        on_hand: shortage.on_hand,
+                    })
+            })
+            .collect();
+
+        let checks = names
+            .iter()
+            .map(|name| {
+                let gathered = options.checklist.is_some()
+                    && table.section_id != "overview"
+                    && table.kind == TableKind::Input;
+                gathered.then(|| Cell::Check((*name).cloned().unwrap_or_default()))
+            })
+            .collect();
+
+        InventoryColumns {
+            checks: Self::shown(checks),
+            images: Self::shown(images),
+            links: Self::shown(links),
+            stock: Self::shown(stock),
+        }
+    }
+
+    fn shown(cells: Vec<Option<Cell>>) -> Option<Vec<Option<Cell>>> {
+        if cells.iter().any(Option::is_some) {
+            Some(cells)
+        } else {
+            None
+        }
+    }
+
+    fn headers(&self, headers: &[&'static str]) -> Vec<&'static str> {
+        let mut all = Vec::new();
+        if self.checks.is_some() {
+            all.push("✓");
+        }
+        if self.images.is_some() {
+            all.push("Image");
+        }
+        all.extend_from_slice(headers);
+        if self.stock.is_some() {
+            all.push("Stock");
+        }
+        if self.links.is_some() {
+            all.push("Links");
+        }
+        all
+    }
+
+    // Rendered cells of one row with the optional columns added around them
+    fn row(&self, row_idx: usize, cells: Vec<String>, render: fn(&Cell) -> String) -> Vec<String> {
+        let cell = |column: &Option<Vec<Option<Cell>>>| {
+            column
+                .as_ref()
+                .map(|cells| cells[row_idx].as_ref().map(render).unwrap_or_default())
+        };
+
+        let mut all = Vec::new();
+        all.extend(cell(&self.checks));
+        all.extend(cell(&self.images));
+        all.extend(cells);
+        all.extend(cell(&self.stock));
+        all.extend(cell(&self.links));
+        all
+    }
+}
+
 fn generate_fasteners_table(
     parts: &[PartReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if parts.is_empty() {
         return String::new();
@@ -1227,36 +4059,38 @@ fn generate_fasteners_table(
     let mut sorted_parts = parts.to_vec();
     sorted_parts.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_parts.iter().any(|p| !inventory.fasteners.contains_key(&p.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"hardware-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>🔩 Hardware</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "hardware",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for part_ref in &sorted_parts {
         if let Some(part) = inventory.fasteners.get(&part_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                part.part_number,
-                part.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("hardware", &part.part_number, inventory),
+                Cell::description(part.description.as_deref()),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         } else {
             warn!("Hardware '{}' not found in inventory", part_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Hardware not found in inventory</span></td><td>{}</td></tr>\n",
-                part_ref.name, part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("hardware", &part_ref.name, inventory),
+                Cell::NotFound("Hardware"),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn generate_electronics_table(
     parts: &[PartReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if parts.is_empty() {
         return String::new();
@@ -1265,36 +4099,38 @@ fn generate_electronics_table(
     let mut sorted_parts = parts.to_vec();
     sorted_parts.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_parts.iter().any(|p| !inventory.electronics.contains_key(&p.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"electronics-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>🔌 Electronics</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "electronics",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for part_ref in &sorted_parts {
         if let Some(part) = inventory.electronics.get(&part_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                part.part_number,
-                part.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("electronics", &part.part_number, inventory),
+                Cell::description(part.description.as_deref()),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         } else {
             warn!("Electronic component '{}' not found in inventory", part_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Electronic component not found in inventory</span></td><td>{}</td></tr>\n",
-                part_ref.name, part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("electronics", &part_ref.name, inventory),
+                Cell::NotFound("Electronic component"),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn generate_custom_parts_table(
     parts: &[PartReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if parts.is_empty() {
         return String::new();
@@ -1303,36 +4139,38 @@ fn generate_custom_parts_table(
     let mut sorted_parts = parts.to_vec();
     sorted_parts.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_parts.iter().any(|p| !inventory.custom_parts.contains_key(&p.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"custom_parts-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>⚙️ Custom Parts</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "custom_parts",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for part_ref in &sorted_parts {
         if let Some(part) = inventory.custom_parts.get(&part_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                part.part_number,
-                part.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("custom_parts", &part.part_number, inventory),
+                Cell::description(part.description.as_deref()),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         } else {
             warn!("Custom part '{}' not found in inventory", part_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Custom part not found in inventory</span></td><td>{}</td></tr>\n",
-                part_ref.name, part_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("custom_parts", &part_ref.name, inventory),
+                Cell::NotFound("Custom part"),
+                Cell::Quantity(part_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn generate_consumables_table(
     consumables: &[ConsumableReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if consumables.is_empty() {
         return String::new();
@@ -1341,35 +4179,36 @@ fn generate_consumables_table(
     let mut sorted_consumables = consumables.to_vec();
     sorted_consumables.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_consumables.iter().any(|c| !inventory.consumables.contains_key(&c.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"consumables-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>🧪 Consumables</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "consumables",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description"],
+    );
 
     for consumable_ref in &sorted_consumables {
         if let Some(consumable) = inventory.consumables.get(&consumable_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td></tr>\n",
-                consumable.part_number,
-                consumable.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>")
-            ));
+            table.push_row(vec![
+                options.name_cell("consumables", &consumable.part_number, inventory),
+                Cell::description(consumable.description.as_deref()),
+            ]);
         } else {
             warn!("Consumable '{}' not found in inventory", consumable_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Consumable not found in inventory</span></td></tr>\n",
-                consumable_ref.name
-            ));
+            table.push_row(vec![
+                options.name_cell("consumables", &consumable_ref.name, inventory),
+                Cell::NotFound("Consumable"),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn generate_tools_table(
-    tools: &[ToolReference],
+    tools: &[CombinedTool],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if tools.is_empty() {
         return String::new();
@@ -1378,37 +4217,49 @@ fn generate_tools_table(
     let mut sorted_tools = tools.to_vec();
     sorted_tools.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_tools.iter().any(|t| !inventory.tools.contains_key(&t.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"tools-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>🔧 Tools</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Setting</th><th>Brand</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "tools",
+        section_id,
+        TableKind::Input,
+        &["Name", "Setting", "Brand"],
+    );
 
     for tool_ref in &sorted_tools {
+        let setting = if tool_ref.settings.is_empty() {
+            "-".to_string()
+        } else {
+            tool_ref
+                .settings
+                .iter()
+                .map(|setting| setting.display(options.torque_units))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let setting = Cell::Text(setting);
         if let Some(tool) = inventory.tools.get(&tool_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                tool.name,
-                tool_ref.setting.as_deref().unwrap_or("-"),
-                tool.brand.as_deref().unwrap_or("-")
-            ));
+            table.push_row(vec![
+                options.name_cell("tools", &tool.name, inventory),
+                setting,
+                Cell::Text(tool.brand.as_deref().unwrap_or("-").to_string()),
+            ]);
         } else {
             warn!("Tool '{}' not found in inventory", tool_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td><span style=\"color: #e53935;\">Tool not found in inventory</span></td></tr>\n",
-                tool_ref.name,
-                tool_ref.setting.as_deref().unwrap_or("-")
-            ));
+            table.push_row(vec![
+                options.name_cell("tools", &tool_ref.name, inventory),
+                setting,
+                Cell::NotFound("Tool"),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn generate_assemblies_table(
     assemblies: &[AssemblyReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if assemblies.is_empty() {
         return String::new();
@@ -1417,35 +4268,35 @@ fn generate_assemblies_table(
     let mut sorted_assemblies = assemblies.to_vec();
     sorted_assemblies.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_assemblies.iter().any(|a| !inventory.assemblies.contains_key(&a.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"assemblies-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>\u{1f4e6} Assemblies</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "assemblies",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for assembly_ref in &sorted_assemblies {
         if let Some(assembly) = inventory.assemblies.get(&assembly_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                assembly.name,
-                assembly.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                assembly_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("assemblies", &assembly.name, inventory),
+                Cell::description(assembly.description.as_deref()),
+                Cell::Quantity(assembly_ref.quantity),
+            ]);
         } else {
             warn!("Assembly '{}' not found in inventory", assembly_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Assembly not found in inventory</span></td><td>{}</td></tr>\n",
-                assembly_ref.name, assembly_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("assemblies", &assembly_ref.name, inventory),
+                Cell::NotFound("Assembly"),
+                Cell::Quantity(assembly_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn combine_assemblies(assemblies: &[AssemblyReference]) -> Vec<AssemblyReference> {
-    let mut combined: std::collections::HashMap<String, (u32, bool, bool)> =
-        std::collections::HashMap::new();
+    let mut combined: BTreeMap<String, (u32, bool, bool)> = BTreeMap::new();
 
     for assembly in assemblies {
         combined
@@ -1473,6 +4324,7 @@ fn generate_units_table(
     units: &[UnitReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if units.is_empty() {
         return String::new();
@@ -1481,35 +4333,35 @@ fn generate_units_table(
     let mut sorted_units = units.to_vec();
     sorted_units.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_units.iter().any(|u| !inventory.units.contains_key(&u.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"units-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>\u{2b50} Units</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "units",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for unit_ref in &sorted_units {
         if let Some(unit) = inventory.units.get(&unit_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                unit.name,
-                unit.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                unit_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("units", &unit.name, inventory),
+                Cell::description(unit.description.as_deref()),
+                Cell::Quantity(unit_ref.quantity),
+            ]);
         } else {
             warn!("Unit '{}' not found in inventory", unit_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Unit not found in inventory</span></td><td>{}</td></tr>\n",
-                unit_ref.name, unit_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("units", &unit_ref.name, inventory),
+                Cell::NotFound("Unit"),
+                Cell::Quantity(unit_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
 fn combine_units(units: &[UnitReference]) -> Vec<UnitReference> {
-    let mut combined: std::collections::HashMap<String, (u32, bool, bool)> =
-        std::collections::HashMap::new();
+    let mut combined: BTreeMap<String, (u32, bool, bool)> = BTreeMap::new();
 
     for unit in units {
         combined
@@ -1537,6 +4389,7 @@ fn generate_subassemblies_table(
     subassemblies: &[SubassemblyReference],
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     if subassemblies.is_empty() {
         return String::new();
@@ -1545,47 +4398,46 @@ fn generate_subassemblies_table(
     let mut sorted_subassemblies = subassemblies.to_vec();
     sorted_subassemblies.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let has_missing = sorted_subassemblies.iter().any(|s| !inventory.subassemblies.contains_key(&s.name));
-    let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-    let mut table = String::from(&format!("<details id=\"subassemblies-{}\" style=\"border-left: 3px solid #f9a825; padding-left: 12px;\">\n<summary><strong{}>\u{1f9e9} Subassemblies</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+    let mut table = ComponentTable::new(
+        "subassemblies",
+        section_id,
+        TableKind::Input,
+        &["Name", "Description", "Quantity"],
+    );
 
     for subassembly_ref in &sorted_subassemblies {
         if let Some(subassembly) = inventory.subassemblies.get(&subassembly_ref.name) {
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                subassembly.name,
-                subassembly.description.as_deref().unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
-                subassembly_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("subassemblies", &subassembly.name, inventory),
+                Cell::description(subassembly.description.as_deref()),
+                Cell::Quantity(subassembly_ref.quantity),
+            ]);
         } else {
             warn!("Subassembly '{}' not found in inventory", subassembly_ref.name);
-            table.push_str(&format!(
-                "<tr><td>{}</td><td><span style=\"color: #e53935;\">Subassembly not found in inventory</span></td><td>{}</td></tr>\n",
-                subassembly_ref.name, subassembly_ref.quantity
-            ));
+            table.push_row(vec![
+                options.name_cell("subassemblies", &subassembly_ref.name, inventory),
+                Cell::NotFound("Subassembly"),
+                Cell::Quantity(subassembly_ref.quantity),
+            ]);
         }
     }
 
-    table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
-    table
+    table.render(options, inventory)
 }
 
-fn generate_labeled_divider(label: &str) -> String {
-    format!(
-        "<div style=\"display: flex; align-items: center; margin: 16px 0 8px 0;\">\
-        <div style=\"flex: 1; height: 1px; background: var(--icons, #747474);\"></div>\
-        <span style=\"padding: 0 12px; color: var(--icons, #747474); font-size: 13px; font-weight: 600; text-transform: uppercase; letter-spacing: 1px;\">{}</span>\
-        <div style=\"flex: 1; height: 1px; background: var(--icons, #747474);\"></div>\
-        </div>",
-        label
-    )
+fn generate_labeled_divider(label: &str, mode: RenderMode) -> String {
+    match mode {
+        RenderMode::Html => format!("<div class=\"bom-divider\"><span>{}</span></div>", label),
+        RenderMode::Markdown => format!("**{}**\n", label.to_uppercase()),
+        RenderMode::None => String::new(),
+    }
 }
 
 fn generate_output_table(
     output: Option<&OutputMetadata>,
     inventory: &Inventory,
     section_id: &str,
+    options: &TableOptions,
 ) -> String {
     let output = match output {
         Some(o) => o,
@@ -1608,115 +4460,122 @@ fn generate_output_table(
     let mut table = String::new();
 
     // Labeled divider between input components and output
-    table.push_str(&generate_labeled_divider("Output"));
+    table.push_str(&generate_labeled_divider("Output", options.mode));
     table.push('\n');
 
     // Output custom parts table with colored left border
     if !sorted_custom_parts.is_empty() {
-        let has_missing = sorted_custom_parts.iter().any(|p| !inventory.custom_parts.contains_key(&p.name));
-        let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-        table.push_str(&format!("<details id=\"output_custom_parts-{}\" style=\"border-left: 3px solid #4caf50; padding-left: 12px;\">\n<summary><strong{}>\u{2699}\u{fe0f} Custom Parts</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+        let mut custom_parts_table = ComponentTable::new(
+            "output_custom_parts",
+            section_id,
+            TableKind::Output,
+            &["Name", "Description", "Quantity"],
+        );
 
         for part_ref in &sorted_custom_parts {
             let description = match inventory.custom_parts.get(&part_ref.name) {
-                Some(p) => p.description.as_deref()
-                    .unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
+                Some(p) => Cell::description(p.description.as_deref()),
                 None => {
                     warn!("Output custom part '{}' not found in inventory", part_ref.name);
-                    "<span style=\"color: #e53935;\">Custom part not found in inventory</span>"
+                    Cell::NotFound("Custom part")
                 }
             };
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                part_ref.name, description, part_ref.quantity
-            ));
+            custom_parts_table.push_row(vec![
+                options.name_cell("custom_parts", &part_ref.name, inventory),
+                description,
+                Cell::Quantity(part_ref.quantity),
+            ]);
         }
 
-        table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
+        table.push_str(&custom_parts_table.render(options, inventory));
     }
 
     // Output assemblies table with colored left border
     if !sorted_assemblies.is_empty() {
-        let has_missing = sorted_assemblies.iter().any(|a| !inventory.assemblies.contains_key(&a.name));
-        let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-        table.push_str(&format!("<details id=\"output_assemblies-{}\" style=\"border-left: 3px solid #4caf50; padding-left: 12px;\">\n<summary><strong{}>\u{1f4e6} Assemblies</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+        let mut assemblies_table = ComponentTable::new(
+            "output_assemblies",
+            section_id,
+            TableKind::Output,
+            &["Name", "Description", "Quantity"],
+        );
 
         for assembly_ref in &sorted_assemblies {
             let description = match inventory.assemblies.get(&assembly_ref.name) {
-                Some(a) => a.description.as_deref()
-                    .unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
+                Some(a) => Cell::description(a.description.as_deref()),
                 None => {
                     warn!("Output assembly '{}' not found in inventory", assembly_ref.name);
-                    "<span style=\"color: #e53935;\">Assembly not found in inventory</span>"
+                    Cell::NotFound("Assembly")
                 }
             };
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                assembly_ref.name, description, assembly_ref.quantity
-            ));
+            assemblies_table.push_row(vec![
+                options.name_cell("assemblies", &assembly_ref.name, inventory),
+                description,
+                Cell::Quantity(assembly_ref.quantity),
+            ]);
         }
 
-        table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
+        table.push_str(&assemblies_table.render(options, inventory));
     }
 
     // Output subassemblies table with colored left border
     if !sorted_subassemblies.is_empty() {
-        let has_missing = sorted_subassemblies.iter().any(|s| !inventory.subassemblies.contains_key(&s.name));
-        let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-        table.push_str(&format!("<details id=\"output_subassemblies-{}\" style=\"border-left: 3px solid #4caf50; padding-left: 12px;\">\n<summary><strong{}>\u{1f9e9} Subassemblies</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+        let mut subassemblies_table = ComponentTable::new(
+            "output_subassemblies",
+            section_id,
+            TableKind::Output,
+            &["Name", "Description", "Quantity"],
+        );
 
         for subassembly_ref in &sorted_subassemblies {
             let description = match inventory.subassemblies.get(&subassembly_ref.name) {
-                Some(s) => s.description.as_deref()
-                    .unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
+                Some(s) => Cell::description(s.description.as_deref()),
                 None => {
                     warn!("Output subassembly '{}' not found in inventory", subassembly_ref.name);
-                    "<span style=\"color: #e53935;\">Subassembly not found in inventory</span>"
+                    Cell::NotFound("Subassembly")
                 }
             };
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                subassembly_ref.name, description, subassembly_ref.quantity
-            ));
+            subassemblies_table.push_row(vec![
+                options.name_cell("subassemblies", &subassembly_ref.name, inventory),
+                description,
+                Cell::Quantity(subassembly_ref.quantity),
+            ]);
         }
 
-        table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
+        table.push_str(&subassemblies_table.render(options, inventory));
     }
 
     // Output units table with colored left border
     if !sorted_units.is_empty() {
-        let has_missing = sorted_units.iter().any(|u| !inventory.units.contains_key(&u.name));
-        let title_style = if has_missing { " style=\"color: #e53935;\"" } else { "" };
-
-        table.push_str(&format!("<details id=\"output_units-{}\" style=\"border-left: 3px solid #4caf50; padding-left: 12px;\">\n<summary><strong{}>\u{2b50} Units</strong></summary>\n<br>\n<table style=\"margin: 0;\">\n<thead>\n<tr><th>Name</th><th>Description</th><th>Quantity</th></tr>\n</thead>\n<tbody>\n", section_id, title_style));
+        let mut units_table = ComponentTable::new(
+            "output_units",
+            section_id,
+            TableKind::Output,
+            &["Name", "Description", "Quantity"],
+        );
 
         for unit_ref in &sorted_units {
             let description = match inventory.units.get(&unit_ref.name) {
-                Some(u) => u.description.as_deref()
-                    .unwrap_or("<span style=\"color: #f9a825;\">No description provided</span>"),
+                Some(u) => Cell::description(u.description.as_deref()),
                 None => {
                     warn!("Output unit '{}' not found in inventory", unit_ref.name);
-                    "<span style=\"color: #e53935;\">Unit not found in inventory</span>"
+                    Cell::NotFound("Unit")
                 }
             };
-            table.push_str(&format!(
-                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
-                unit_ref.name, description, unit_ref.quantity
-            ));
+            units_table.push_row(vec![
+                options.name_cell("units", &unit_ref.name, inventory),
+                description,
+                Cell::Quantity(unit_ref.quantity),
+            ]);
         }
 
-        table.push_str("</tbody>\n</table>\n<br>\n</details>\n\n");
+        table.push_str(&units_table.render(options, inventory));
     }
 
     table
 }
 
 fn combine_subassemblies(subassemblies: &[SubassemblyReference]) -> Vec<SubassemblyReference> {
-    let mut combined: std::collections::HashMap<String, (u32, bool, bool)> =
-        std::collections::HashMap::new();
+    let mut combined: BTreeMap<String, (u32, bool, bool)> = BTreeMap::new();
 
     for subassembly in subassemblies {
         combined
@@ -1742,14 +4601,10 @@ fn combine_subassemblies(subassemblies: &[SubassemblyReference]) -> Vec<Subassem
 
 fn combine_output_metadata(outputs: &[OutputMetadata]) -> OutputMetadata {
     // (quantity, exclude_from_overview)
-    let mut custom_part_map: std::collections::HashMap<String, (u32, bool)> =
-        std::collections::HashMap::new();
-    let mut assembly_map: std::collections::HashMap<String, (u32, bool)> =
-        std::collections::HashMap::new();
-    let mut subassembly_map: std::collections::HashMap<String, (u32, bool)> =
-        std::collections::HashMap::new();
-    let mut unit_map: std::collections::HashMap<String, (u32, bool)> =
-        std::collections::HashMap::new();
+    let mut custom_part_map: BTreeMap<String, (u32, bool)> = BTreeMap::new();
+    let mut assembly_map: BTreeMap<String, (u32, bool)> = BTreeMap::new();
+    let mut subassembly_map: BTreeMap<String, (u32, bool)> = BTreeMap::new();
+    let mut unit_map: BTreeMap<String, (u32, bool)> = BTreeMap::new();
 
     for output in outputs {
         if let Some(custom_parts) = &output.custom_parts {
@@ -1884,6 +4739,8 @@ fn accumulate_subassemblies(
                 .and_modify(|item| item.total_quantity += subassembly_ref.quantity)
                 .or_insert_with(|| BomSubassemblyItem {
                     name: inventory_subassembly.name.clone(),
+                    datasheet: inventory_subassembly.datasheet.clone(),
+                    url: inventory_subassembly.url.clone(),
                     description: inventory_subassembly
                         .description
                         .as_deref()
@@ -1913,6 +4770,8 @@ fn accumulate_assemblies(
                 .and_modify(|item| item.total_quantity += assembly_ref.quantity)
                 .or_insert_with(|| BomAssemblyItem {
                     name: inventory_assembly.name.clone(),
+                    datasheet: inventory_assembly.datasheet.clone(),
+                    url: inventory_assembly.url.clone(),
                     description: inventory_assembly
                         .description
                         .as_deref()
@@ -1942,6 +4801,8 @@ fn accumulate_units(
                 .and_modify(|item| item.total_quantity += unit_ref.quantity)
                 .or_insert_with(|| BomUnitItem {
                     name: inventory_unit.name.clone(),
+                    datasheet: inventory_unit.datasheet.clone(),
+                    url: inventory_unit.url.clone(),
                     description: inventory_unit
                         .description
                         .as_deref()
@@ -1970,6 +4831,8 @@ fn accumulate_fasteners(
                 .and_modify(|item| item.total_quantity += part_ref.quantity)
                 .or_insert_with(|| BomFastenerItem {
                     part_number: inventory_part.part_number.clone(),
+                    datasheet: inventory_part.datasheet.clone(),
+                    url: inventory_part.url.clone(),
                     description: inventory_part
                         .description
                         .as_deref()
@@ -2000,6 +4863,8 @@ fn accumulate_electronics(
                 .and_modify(|item| item.total_quantity += part_ref.quantity)
                 .or_insert_with(|| BomElectronicItem {
                     part_number: inventory_part.part_number.clone(),
+                    datasheet: inventory_part.datasheet.clone(),
+                    url: inventory_part.url.clone(),
                     description: inventory_part
                         .description
                         .as_deref()
@@ -2030,6 +4895,8 @@ fn accumulate_custom_parts(
                 .and_modify(|item| item.total_quantity += part_ref.quantity)
                 .or_insert_with(|| BomCustomPartItem {
                     part_number: inventory_part.part_number.clone(),
+                    datasheet: inventory_part.datasheet.clone(),
+                    url: inventory_part.url.clone(),
                     description: inventory_part
                         .description
                         .as_deref()
@@ -2060,6 +4927,8 @@ fn accumulate_consumables(
                 .entry(key)
                 .or_insert_with(|| BomConsumableItem {
                     part_number: inventory_consumable.part_number.clone(),
+                    datasheet: inventory_consumable.datasheet.clone(),
+                    url: inventory_consumable.url.clone(),
                     description: inventory_consumable
                         .description
                         .as_deref()
@@ -2075,6 +4944,7 @@ fn accumulate_consumables(
 fn accumulate_tools(
     tools: &[ToolReference],
     inventory: &Inventory,
+    step: &StepRef,
     all_tools: &mut HashMap<String, BomToolItem>,
 ) {
     for tool_ref in tools {
@@ -2084,28 +4954,305 @@ fn accumulate_tools(
         if let Some(inventory_tool) = inventory.tools.get(&tool_ref.name) {
             let key = tool_ref.name.clone();
 
-            all_tools
-                .entry(key)
-                .and_modify(|item| {
-                    if let Some(setting) = &tool_ref.setting {
-                        if !item.settings.contains(setting) {
-                            item.settings.push(setting.clone());
-                        }
-                    }
-                })
-                .or_insert_with(|| {
-                    let mut settings = Vec::new();
-                    if let Some(setting) = &tool_ref.setting {
-                        settings.push(setting.clone());
+            let item = all_tools.entry(key).or_insert_with(|| BomToolItem {
+                name: inventory_tool.name.clone(),
+                datasheet: inventory_tool.datasheet.clone(),
+                url: inventory_tool.url.clone(),
+                brand: inventory_tool.brand.as_deref().unwrap_or("-").to_string(),
+                settings: Vec::new(),
+                usage: Vec::new(),
+                step_count: 0,
+            });
+            let setting = tool_ref.setting.as_ref().map(ToolSetting::normalized);
+            if let Some(setting) = &setting {
+                if !item.settings.contains(setting) {
+                    item.settings.push(setting.clone());
+                }
+            }
+
+            // A tool listed twice in one step still counts as one step
+            if !item
+                .usage
+                .iter()
+                .any(|usage| usage.step_ids.contains(&step.id))
+            {
+                item.step_count += 1;
+            }
+            match item.usage.iter_mut().find(|usage| usage.setting == setting) {
+                Some(usage) => {
+                    if !usage.step_ids.contains(&step.id) {
+                        usage.steps.push(step.label.clone());
+                        usage.step_ids.push(step.id.clone());
                     }
-                    BomToolItem {
-                        name: inventory_tool.name.clone(),
-                        brand: inventory_tool.brand.as_deref().unwrap_or("-").to_string(),
-                        settings,
+                }
+                None => item.usage.push(ToolUsage {
+                    setting,
+                    steps: vec![step.label.clone()],
+                    step_ids: vec![step.id.clone()],
+                }),
+            }
+        }
+    }
+}
+
+// Item whose stock does not cover the required quantity for the whole build
+#[derive(Debug, Serialize)]
+struct Shortage {
+    category: &'static str,
+    name: String,
+    description: String,
+    required: u32,
+    on_hand: f64,
+    short: u32,
+}
+
+// Compares book-wide totals times the build quantity against the inventory's `On Hand`.
+// Only counted categories are checked, and only items with a stock level. Items the
+// book makes itself only need stock for the part of the total it does not make.
+fn find_shortages(totals: &BomTotals, inventory: &Inventory, build_quantity: u32) -> Vec<Shortage> {
+    let mut required: Vec<(&'static str, &str, &str, u32)> = Vec::new();
+    required.extend(totals.fasteners.values().map(|i| {
+        (
+            "hardware",
+            i.part_number.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+    required.extend(totals.electronics.values().map(|i| {
+        (
+            "electronics",
+            i.part_number.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+    required.extend(totals.custom_parts.values().map(|i| {
+        (
+            "custom_parts",
+            i.part_number.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+    required.extend(totals.assemblies.values().map(|i| {
+        (
+            "assemblies",
+            i.name.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+    required.extend(totals.subassemblies.values().map(|i| {
+        (
+            "subassemblies",
+            i.name.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+    required.extend(totals.units.values().map(|i| {
+        (
+            "units",
+            i.name.as_str(),
+            i.description.as_str(),
+            i.total_quantity,
+        )
+    }));
+
+    let mut shortages: Vec<Shortage> = required
+        .into_iter()
+        .filter_map(|(category, name, description, quantity)| {
+            let on_hand = inventory.on_hand(category, name)?;
+            let produced = totals
+                .produced
+                .get(&(category, name.to_string()))
+                .copied()
+                .unwrap_or(0);
+            let required = quantity
+                .saturating_sub(produced)
+                .saturating_mul(build_quantity);
+            if f64::from(required) <= on_hand {
+                return None;
+            }
+            Some(Shortage {
+                category,
+                name: name.to_string(),
+                description: description.to_string(),
+                required,
+                on_hand,
+                short: (f64::from(required) - on_hand.max(0.0)).ceil() as u32,
+            })
+        })
+        .collect();
+
+    let category_index = |category: &str| BOM_CATEGORIES.iter().position(|c| *c == category);
+    shortages.sort_by(|a, b| {
+        category_index(a.category)
+            .cmp(&category_index(b.category))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    shortages
+}
+
+// Inputs of one chapter, picked and bagged together for its station
+struct ChapterKit {
+    chapter: String,
+    items: Vec<KitItem>,
+}
+
+struct KitItem {
+    category: &'static str,
+    name: String,
+    description: String,
+    // Tools and consumables have no quantities
+    quantity: Option<u32>,
+    bin: Option<String>,
+}
+
+// Every input of a chapter that goes into the BOM, combined across its steps and
+// sorted by category and name
+fn chapter_kit(chapter: &str, metadata: &ChapterMetadata, inventory: &Inventory) -> ChapterKit {
+    let mut items: BTreeMap<(usize, String), KitItem> = BTreeMap::new();
+
+    for section in metadata.sections.values() {
+        let inputs = section
+            .input
+            .as_ref()
+            .map(|input| export_input_items(input, inventory))
+            .unwrap_or_default();
+
+        for item in inputs {
+            // Same rules as the BOM totals: excluded and unknown items are left out
+            if item.exclude_from_bom == Some(true) || item.inventory.is_none() {
+                continue;
+            }
+            let category_index = BOM_CATEGORIES
+                .iter()
+                .position(|c| *c == item.category)
+                .unwrap_or(BOM_CATEGORIES.len());
+
+            items
+                .entry((category_index, item.name.clone()))
+                .and_modify(|kit_item| {
+                    if let (Some(total), Some(quantity)) =
+                        (kit_item.quantity.as_mut(), item.quantity)
+                    {
+                        *total += quantity;
                     }
+                })
+                .or_insert_with(|| KitItem {
+                    category: item.category,
+                    description: inventory
+                        .describe(item.category, &item.name)
+                        .unwrap_or_else(|| "-".to_string()),
+                    quantity: item.quantity,
+                    bin: inventory.bin(item.category, &item.name).map(str::to_string),
+                    name: item.name.clone(),
                 });
         }
     }
+
+    ChapterKit {
+        chapter: chapter.to_string(),
+        items: items.into_values().collect(),
+    }
+}
+
+// One row of the traveler: a step and what it needs, to be signed off per unit
+struct TravelerStep {
+    chapter: String,
+    step: String,
+    parts: Vec<String>,
+    tools: Vec<String>,
+    outputs: Vec<String>,
+}
+
+// Titles and section keys of a chapter's steps in document order, followed by
+// sections without a matching header
+fn chapter_steps(
+    content: &str,
+    metadata: &ChapterMetadata,
+    matcher: &StepMatcher,
+) -> Vec<(String, String)> {
+    let step_headers =
+        find_step_headers(&remove_front_matter(content), &metadata.sections, matcher);
+    // Sub-steps are named after their parents, e.g. `Step 2 › Step 2.1`
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    let mut steps: Vec<(String, String)> = Vec::new();
+    for header in &step_headers {
+        while parents
+            .last()
+            .is_some_and(|(level, _)| *level >= header.level)
+        {
+            parents.pop();
+        }
+        let title = match parents.last() {
+            Some((_, parent)) => format!("{} › {}", parent, header.title),
+            None => header.title.clone(),
+        };
+        parents.push((header.level, title.clone()));
+        steps.push((title, header.key.clone()));
+    }
+    for key in metadata.sections.keys() {
+        if !step_headers.iter().any(|header| header.key == *key) {
+            steps.push((key.clone(), key.clone()));
+        }
+    }
+    steps
+}
+
+// One traveler row per step, with what it uses and makes
+fn traveler_steps(
+    chapter: &str,
+    steps: &[(String, String)],
+    metadata: &ChapterMetadata,
+    inventory: &Inventory,
+    torque_units: &[String],
+) -> Vec<TravelerStep> {
+    steps
+        .iter()
+        .map(|(title, key)| {
+            let section = metadata.sections.get(key);
+            let inputs = section
+                .and_then(|section| section.input.as_ref())
+                .map(|input| export_input_items(input, inventory))
+                .unwrap_or_default();
+            let outputs = section
+                .and_then(|section| section.output.as_ref())
+                .map(|output| export_output_items(output, inventory))
+                .unwrap_or_default();
+
+            let (tools, parts): (Vec<ItemExport>, Vec<ItemExport>) = inputs
+                .into_iter()
+                .partition(|item| item.category == "tools");
+            let describe = |item: &ItemExport| match (item.quantity, &item.setting) {
+                (Some(quantity), _) => format!("{} × {}", quantity, item.name),
+                (None, Some(setting)) => {
+                    format!("{} ({})", item.name, setting.display(torque_units))
+                }
+                (None, None) => item.name.clone(),
+            };
+
+            TravelerStep {
+                chapter: chapter.to_string(),
+                step: title.clone(),
+                parts: parts.iter().map(describe).collect(),
+                tools: tools.iter().map(describe).collect(),
+                outputs: outputs.iter().map(describe).collect(),
+            }
+        })
+        .collect()
+}
+
+// Hash of a chapter's steps and their items, as 16 hex digits. FNV-1a is used
+// because it gives the same value on every build and Rust version.
+fn steps_hash(metadata: &ChapterMetadata) -> String {
+    let steps = serde_json::to_string(&metadata.sections).unwrap_or_default();
+    let hash = steps.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
 }
 
 fn resolve_git_ref(book_root: &std::path::Path) -> Result<String, Error> {
@@ -2140,16 +5287,29 @@ fn resolve_git_ref(book_root: &std::path::Path) -> Result<String, Error> {
     }
 
     Err(Error::msg(
-        "BOM_OUTPUT_PATH contains {git_ref} but could not determine git tag or branch name",
+        "Output path contains {git_ref} but could not determine git tag or branch name",
     ))
 }
 
 fn resolve_output_path(raw_path: &str, book_root: &std::path::Path) -> Result<String, Error> {
     if raw_path.contains("{git_ref}") {
         let git_ref = resolve_git_ref(book_root)?;
-        Ok(raw_path.replace("{git_ref}", &git_ref))
+        Ok(resolve_book_relative_path(
+            &raw_path.replace("{git_ref}", &git_ref),
+            book_root,
+        ))
+    } else {
+        Ok(resolve_book_relative_path(raw_path, book_root))
+    }
+}
+
+// Relative paths are relative to the book root, which is also the working
+// directory mdbook uses when running the preprocessor
+fn resolve_book_relative_path(path: &str, book_root: &std::path::Path) -> String {
+    if path.starts_with("~/") || std::path::Path::new(path).is_absolute() {
+        path.to_string()
     } else {
-        Ok(raw_path.to_string())
+        book_root.join(path).to_string_lossy().to_string()
     }
 }
 
@@ -2166,22 +5326,78 @@ fn create_output_directory_for_path(file_path: &str) -> Result<(), Error> {
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
+// Creation time to stamp into the workbook. SOURCE_DATE_EPOCH wins when set;
+// otherwise reproducible builds use the Unix epoch and the rest use the current time.
+fn workbook_timestamp(reproducible: bool) -> Result<Option<i64>, Error> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.trim().parse::<i64>().map(Some).map_err(|_| {
+            Error::msg(format!(
+                "SOURCE_DATE_EPOCH must be a Unix timestamp, got '{}'",
+                epoch
+            ))
+        }),
+        Err(_) if reproducible => Ok(Some(0)),
+        Err(_) => Ok(None),
+    }
+}
+
+// Datasheet and URL columns after the fixed ones. Each is only added when some item
+// in the sheet has a value; web links become clickable hyperlinks.
+fn write_link_columns(
+    worksheet: &mut Worksheet,
+    first_col: u16,
+    links: &[(Option<&str>, Option<&str>)],
+) -> Result<(), Error> {
+    let datasheets: Vec<Option<&str>> = links.iter().map(|(datasheet, _)| *datasheet).collect();
+    let urls: Vec<Option<&str>> = links.iter().map(|(_, url)| *url).collect();
+    let columns: Vec<(&str, Vec<Option<&str>>)> = [("Datasheet", datasheets), ("URL", urls)]
+        .into_iter()
+        .filter(|(_, values)| values.iter().any(Option::is_some))
+        .collect();
+
+    for (offset, (header, values)) in columns.iter().enumerate() {
+        let col = first_col + offset as u16;
+        worksheet
+            .write_string(0, col, *header)
+            .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        for (row, value) in values.iter().enumerate() {
+            let row = row as u32 + 1; // Skip header row
+            match value {
+                Some(link) if link.starts_with("http://") || link.starts_with("https://") => {
+                    worksheet.write_url(row, col, *link).map_err(|e| {
+                        Error::msg(format!("Failed to write link '{}': {}", link, e))
+                    })?;
+                }
+                Some(path) => {
+                    worksheet
+                        .write_string(row, col, *path)
+                        .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+                }
+                None => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 fn generate_bom_excel_file(
-    fasteners: &HashMap<String, BomFastenerItem>,
-    electronics: &HashMap<String, BomElectronicItem>,
-    custom_parts: &HashMap<String, BomCustomPartItem>,
-    consumables: &HashMap<String, BomConsumableItem>,
-    tools: &HashMap<String, BomToolItem>,
-    assemblies: &HashMap<String, BomAssemblyItem>,
-    subassemblies: &HashMap<String, BomSubassemblyItem>,
-    units: &HashMap<String, BomUnitItem>,
+    totals: &BomTotals,
+    shortages: &[Shortage],
+    kits: &[ChapterKit],
+    torque_units: &[String],
     output_path: &str,
+    created_at: Option<i64>,
 ) -> Result<(), Error> {
     let mut workbook = Workbook::new();
 
+    if let Some(timestamp) = created_at {
+        let datetime = ExcelDateTime::from_timestamp(timestamp)
+            .map_err(|e| Error::msg(format!("Invalid workbook timestamp {}: {}", timestamp, e)))?;
+        workbook.set_properties(&DocProperties::new().set_creation_datetime(&datetime));
+    }
+
     // Generate Hardware sheet
-    if !fasteners.is_empty() {
+    if !totals.fasteners.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Hardware")
@@ -2199,8 +5415,12 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_fasteners: Vec<_> = fasteners.values().collect();
-        sorted_fasteners.sort_by(|a, b| a.description.cmp(&b.description));
+        let mut sorted_fasteners: Vec<_> = totals.fasteners.values().collect();
+        sorted_fasteners.sort_by(|a, b| {
+            a.description
+                .cmp(&b.description)
+                .then_with(|| a.part_number.cmp(&b.part_number))
+        });
 
         for (row, fastener) in sorted_fasteners.iter().enumerate() {
             let row = row + 1; // Skip header row
@@ -2214,10 +5434,18 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, fastener.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_fasteners
+                .iter()
+                .map(|fastener| (fastener.datasheet.as_deref(), fastener.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Electronics sheet
-    if !electronics.is_empty() {
+    if !totals.electronics.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Electronics")
@@ -2235,8 +5463,12 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_electronics: Vec<_> = electronics.values().collect();
-        sorted_electronics.sort_by(|a, b| a.description.cmp(&b.description));
+        let mut sorted_electronics: Vec<_> = totals.electronics.values().collect();
+        sorted_electronics.sort_by(|a, b| {
+            a.description
+                .cmp(&b.description)
+                .then_with(|| a.part_number.cmp(&b.part_number))
+        });
 
         for (row, electronic) in sorted_electronics.iter().enumerate() {
             let row = row + 1; // Skip header row
@@ -2250,10 +5482,18 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, electronic.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_electronics
+                .iter()
+                .map(|electronic| (electronic.datasheet.as_deref(), electronic.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Custom Parts sheet
-    if !custom_parts.is_empty() {
+    if !totals.custom_parts.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Custom Parts")
@@ -2271,8 +5511,12 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_custom_parts: Vec<_> = custom_parts.values().collect();
-        sorted_custom_parts.sort_by(|a, b| a.description.cmp(&b.description));
+        let mut sorted_custom_parts: Vec<_> = totals.custom_parts.values().collect();
+        sorted_custom_parts.sort_by(|a, b| {
+            a.description
+                .cmp(&b.description)
+                .then_with(|| a.part_number.cmp(&b.part_number))
+        });
 
         for (row, custom_part) in sorted_custom_parts.iter().enumerate() {
             let row = row + 1; // Skip header row
@@ -2286,40 +5530,79 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, custom_part.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_custom_parts
+                .iter()
+                .map(|custom_part| (custom_part.datasheet.as_deref(), custom_part.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Tools sheet
-    if !tools.is_empty() {
+    if !totals.tools.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Tools")
             .map_err(|e| Error::msg(format!("Failed to set sheet name: {}", e)))?;
 
         // Headers
-        worksheet
-            .write_string(0, 0, "Name")
-            .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
-        worksheet
-            .write_string(0, 1, "Brand")
-            .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        for (col, header) in ["Name", "Brand", "Steps", "Setting", "Used In"]
+            .iter()
+            .enumerate()
+        {
+            worksheet
+                .write_string(0, col as u16, *header)
+                .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        }
 
-        // Data
-        let mut sorted_tools: Vec<_> = tools.values().collect();
+        // Data, one row per distinct setting so each preset can be prepared separately
+        let mut sorted_tools: Vec<_> = totals.tools.values().collect();
         sorted_tools.sort_by(|a, b| a.name.cmp(&b.name));
-
-        for (row, tool) in sorted_tools.iter().enumerate() {
-            let row = row + 1; // Skip header row
+        let rows: Vec<(&BomToolItem, &ToolUsage)> = sorted_tools
+            .iter()
+            .flat_map(|tool| tool.usage.iter().map(move |usage| (*tool, usage)))
+            .collect();
+        let wrap = Format::new().set_text_wrap().set_align(FormatAlign::Top);
+
+        for (row, (tool, usage)) in rows.iter().enumerate() {
+            let row = row as u32 + 1; // Skip header row
+            let setting = usage
+                .setting
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |setting| setting.display(torque_units));
+            worksheet
+                .write_string(row, 0, &tool.name)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row, 1, &tool.brand)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_number(row, 2, tool.step_count)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
             worksheet
-                .write_string(row as u32, 0, &tool.name)
+                .write_string(row, 3, &setting)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
             worksheet
-                .write_string(row as u32, 1, &tool.brand)
+                .write_string_with_format(row, 4, usage.steps.join("\n"), &wrap)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        worksheet
+            .set_column_width(4, 50)
+            .map_err(|e| Error::msg(format!("Failed to set column width: {}", e)))?;
+        write_link_columns(
+            worksheet,
+            5,
+            &rows
+                .iter()
+                .map(|(tool, _)| (tool.datasheet.as_deref(), tool.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Consumables sheet
-    if !consumables.is_empty() {
+    if !totals.consumables.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Consumables")
@@ -2334,8 +5617,12 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_consumables: Vec<_> = consumables.values().collect();
-        sorted_consumables.sort_by(|a, b| a.description.cmp(&b.description));
+        let mut sorted_consumables: Vec<_> = totals.consumables.values().collect();
+        sorted_consumables.sort_by(|a, b| {
+            a.description
+                .cmp(&b.description)
+                .then_with(|| a.part_number.cmp(&b.part_number))
+        });
 
         for (row, consumable) in sorted_consumables.iter().enumerate() {
             let row = row + 1; // Skip header row
@@ -2346,10 +5633,18 @@ fn generate_bom_excel_file(
                 .write_string(row as u32, 1, &consumable.description)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            2,
+            &sorted_consumables
+                .iter()
+                .map(|consumable| (consumable.datasheet.as_deref(), consumable.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Assemblies sheet
-    if !assemblies.is_empty() {
+    if !totals.assemblies.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Assemblies")
@@ -2367,7 +5662,7 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_assemblies: Vec<_> = assemblies.values().collect();
+        let mut sorted_assemblies: Vec<_> = totals.assemblies.values().collect();
         sorted_assemblies.sort_by(|a, b| a.name.cmp(&b.name));
 
         for (row, assembly) in sorted_assemblies.iter().enumerate() {
@@ -2382,10 +5677,18 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, assembly.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_assemblies
+                .iter()
+                .map(|assembly| (assembly.datasheet.as_deref(), assembly.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Subassemblies sheet
-    if !subassemblies.is_empty() {
+    if !totals.subassemblies.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Subassemblies")
@@ -2403,7 +5706,7 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_subassemblies: Vec<_> = subassemblies.values().collect();
+        let mut sorted_subassemblies: Vec<_> = totals.subassemblies.values().collect();
         sorted_subassemblies.sort_by(|a, b| a.name.cmp(&b.name));
 
         for (row, subassembly) in sorted_subassemblies.iter().enumerate() {
@@ -2418,10 +5721,18 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, subassembly.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_subassemblies
+                .iter()
+                .map(|subassembly| (subassembly.datasheet.as_deref(), subassembly.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
     }
 
     // Generate Units sheet
-    if !units.is_empty() {
+    if !totals.units.is_empty() {
         let worksheet = workbook
             .add_worksheet()
             .set_name("Units")
@@ -2439,7 +5750,7 @@ fn generate_bom_excel_file(
             .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
 
         // Data
-        let mut sorted_units: Vec<_> = units.values().collect();
+        let mut sorted_units: Vec<_> = totals.units.values().collect();
         sorted_units.sort_by(|a, b| a.name.cmp(&b.name));
 
         for (row, unit) in sorted_units.iter().enumerate() {
@@ -2454,6 +5765,117 @@ fn generate_bom_excel_file(
                 .write_number(row as u32, 2, unit.total_quantity as f64)
                 .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
         }
+        write_link_columns(
+            worksheet,
+            3,
+            &sorted_units
+                .iter()
+                .map(|unit| (unit.datasheet.as_deref(), unit.url.as_deref()))
+                .collect::<Vec<_>>(),
+        )?;
+    }
+
+    // Generate Kitting sheet, one block of rows per chapter in book order
+    if !kits.is_empty() {
+        let worksheet = workbook
+            .add_worksheet()
+            .set_name("Kitting")
+            .map_err(|e| Error::msg(format!("Failed to set sheet name: {}", e)))?;
+
+        // Bin locations are optional in the inventory
+        let has_bins = kits
+            .iter()
+            .flat_map(|kit| &kit.items)
+            .any(|item| item.bin.is_some());
+        let mut headers = vec!["Chapter", "Category", "Name", "Description", "Quantity"];
+        if has_bins {
+            headers.push("Bin");
+        }
+
+        // Headers
+        for (col, header) in headers.iter().enumerate() {
+            worksheet
+                .write_string(0, col as u16, *header)
+                .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        }
+
+        // Data
+        let rows = kits
+            .iter()
+            .flat_map(|kit| kit.items.iter().map(move |item| (&kit.chapter, item)));
+        for (row, (chapter, item)) in rows.enumerate() {
+            let row = row + 1; // Skip header row
+            worksheet
+                .write_string(row as u32, 0, chapter)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row as u32, 1, category_label(item.category))
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row as u32, 2, &item.name)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row as u32, 3, &item.description)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            if let Some(quantity) = item.quantity {
+                worksheet
+                    .write_number(row as u32, 4, quantity as f64)
+                    .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            }
+            if let Some(bin) = &item.bin {
+                worksheet
+                    .write_string(row as u32, 5, bin)
+                    .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            }
+        }
+    }
+
+    // Generate Shortages sheet
+    if !shortages.is_empty() {
+        let worksheet = workbook
+            .add_worksheet()
+            .set_name("Shortages")
+            .map_err(|e| Error::msg(format!("Failed to set sheet name: {}", e)))?;
+
+        // Headers
+        for (col, header) in [
+            "Category",
+            "Name",
+            "Description",
+            "Required",
+            "On Hand",
+            "Short",
+        ]
+        .iter()
+        .enumerate()
+        {
+            worksheet
+                .write_string(0, col as u16, *header)
+                .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        }
+
+        // Data, already sorted by category and name
+        for (row, shortage) in shortages.iter().enumerate() {
+            let row = row + 1; // Skip header row
+            worksheet
+                .write_string(row as u32, 0, category_label(shortage.category))
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row as u32, 1, &shortage.name)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_string(row as u32, 2, &shortage.description)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_number(row as u32, 3, shortage.required as f64)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_number(row as u32, 4, shortage.on_hand)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            worksheet
+                .write_number(row as u32, 5, shortage.short as f64)
+                .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+        }
     }
 
     workbook
@@ -2462,3 +5884,1461 @@ fn generate_bom_excel_file(
 
     Ok(())
 }
+
+fn generate_traveler_excel_file(
+    steps: &[TravelerStep],
+    output_path: &str,
+    created_at: Option<i64>,
+) -> Result<(), Error> {
+    let mut workbook = Workbook::new();
+
+    if let Some(timestamp) = created_at {
+        let datetime = ExcelDateTime::from_timestamp(timestamp)
+            .map_err(|e| Error::msg(format!("Invalid workbook timestamp {}: {}", timestamp, e)))?;
+        workbook.set_properties(&DocProperties::new().set_creation_datetime(&datetime));
+    }
+
+    let worksheet = workbook
+        .add_worksheet()
+        .set_name("Traveler")
+        .map_err(|e| Error::msg(format!("Failed to set sheet name: {}", e)))?;
+
+    // Item lists go one per line; the sign-off columns stay empty for the operator
+    let header_format = Format::new().set_bold().set_border(FormatBorder::Thin);
+    let cell_format = Format::new()
+        .set_text_wrap()
+        .set_align(FormatAlign::Top)
+        .set_border(FormatBorder::Thin);
+    let columns = [
+        ("Chapter", 20.0),
+        ("Step", 30.0),
+        ("Parts", 30.0),
+        ("Tools", 25.0),
+        ("Output", 25.0),
+        ("Operator", 12.0),
+        ("Date", 12.0),
+        ("Serial / Lot", 18.0),
+    ];
+
+    // Headers
+    for (col, (header, width)) in columns.iter().enumerate() {
+        worksheet
+            .write_string_with_format(0, col as u16, *header, &header_format)
+            .map_err(|e| Error::msg(format!("Failed to write header: {}", e)))?;
+        worksheet
+            .set_column_width(col as u16, *width)
+            .map_err(|e| Error::msg(format!("Failed to set column width: {}", e)))?;
+    }
+    worksheet
+        .set_freeze_panes(1, 0)
+        .map_err(|e| Error::msg(format!("Failed to freeze header row: {}", e)))?;
+    worksheet
+        .set_repeat_rows(0, 0)
+        .map_err(|e| Error::msg(format!("Failed to repeat header row: {}", e)))?;
+    worksheet.set_landscape();
+
+    // Data
+    for (row, step) in steps.iter().enumerate() {
+        let row = row as u32 + 1; // Skip header row
+        let values = [
+            step.chapter.clone(),
+            step.step.clone(),
+            step.parts.join("\n"),
+            step.tools.join("\n"),
+            step.outputs.join("\n"),
+            String::new(),
+            String::new(),
+            String::new(),
+        ];
+        for (col, value) in values.iter().enumerate() {
+            if value.is_empty() {
+                // Keeps the border on cells left for handwriting
+                worksheet
+                    .write_blank(row, col as u16, &cell_format)
+                    .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            } else {
+                worksheet
+                    .write_string_with_format(row, col as u16, value, &cell_format)
+                    .map_err(|e| Error::msg(format!("Failed to write data: {}", e)))?;
+            }
+        }
+    }
+
+    workbook
+        .save(output_path)
+        .map_err(|e| Error::msg(format!("Failed to save Excel file: {}", e)))?;
+
+    Ok(())
+}
+
+// Version of the JSON export format, bumped on breaking changes to its structure
+const BOM_JSON_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize)]
+struct BomExport<'a> {
+    schema_version: u32,
+    generator: String,
+    chapters: &'a [ChapterExport],
+    totals: BomTotalsExport<'a>,
+    build_quantity: u32,
+    shortages: &'a [Shortage],
+    missing: &'a [MissingItem],
+}
+
+#[derive(Debug, Serialize)]
+struct ChapterExport {
+    name: String,
+    path: Option<String>,
+    exclude_from_bom: bool,
+    steps: Vec<StepExport>,
+}
+
+#[derive(Debug, Serialize)]
+struct StepExport {
+    key: String,
+    inputs: Vec<ItemExport>,
+    outputs: Vec<ItemExport>,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemExport {
+    category: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quantity: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    setting: Option<ToolSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exclude_from_bom: Option<bool>,
+    exclude_from_overview: bool,
+    inventory: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct MissingItem {
+    category: &'static str,
+    name: String,
+    role: &'static str,
+    chapter: String,
+    step: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BomTotalsExport<'a> {
+    hardware: Vec<&'a BomFastenerItem>,
+    electronics: Vec<&'a BomElectronicItem>,
+    custom_parts: Vec<&'a BomCustomPartItem>,
+    consumables: Vec<&'a BomConsumableItem>,
+    tools: Vec<&'a BomToolItem>,
+    assemblies: Vec<&'a BomAssemblyItem>,
+    subassemblies: Vec<&'a BomSubassemblyItem>,
+    units: Vec<&'a BomUnitItem>,
+}
+
+fn export_item<T: Serialize>(
+    category: &'static str,
+    name: &str,
+    quantity: Option<u32>,
+    setting: Option<&ToolSetting>,
+    exclude_from_bom: Option<bool>,
+    exclude_from_overview: bool,
+    record: Option<&T>,
+) -> ItemExport {
+    ItemExport {
+        category,
+        name: name.to_string(),
+        quantity,
+        setting: setting.map(ToolSetting::normalized),
+        exclude_from_bom,
+        exclude_from_overview,
+        inventory: record.and_then(|r| serde_json::to_value(r).ok()),
+    }
+}
+
+fn export_input_items(input: &InputMetadata, inventory: &Inventory) -> Vec<ItemExport> {
+    let mut items = Vec::new();
+
+    for p in input.hardware.iter().flatten() {
+        items.push(export_item(
+            "hardware",
+            &p.name,
+            Some(p.quantity),
+            None,
+            Some(p.exclude_from_bom),
+            p.exclude_from_overview,
+            inventory.fasteners.get(&p.name),
+        ));
+    }
+    for p in input.electronics.iter().flatten() {
+        items.push(export_item(
+            "electronics",
+            &p.name,
+            Some(p.quantity),
+            None,
+            Some(p.exclude_from_bom),
+            p.exclude_from_overview,
+            inventory.electronics.get(&p.name),
+        ));
+    }
+    for p in input.custom_parts.iter().flatten() {
+        items.push(export_item(
+            "custom_parts",
+            &p.name,
+            Some(p.quantity),
+            None,
+            Some(p.exclude_from_bom),
+            p.exclude_from_overview,
+            inventory.custom_parts.get(&p.name),
+        ));
+    }
+    for s in input.subassemblies.iter().flatten() {
+        items.push(export_item(
+            "subassemblies",
+            &s.name,
+            Some(s.quantity),
+            None,
+            Some(s.exclude_from_bom),
+            s.exclude_from_overview,
+            inventory.subassemblies.get(&s.name),
+        ));
+    }
+    for a in input.assemblies.iter().flatten() {
+        items.push(export_item(
+            "assemblies",
+            &a.name,
+            Some(a.quantity),
+            None,
+            Some(a.exclude_from_bom),
+            a.exclude_from_overview,
+            inventory.assemblies.get(&a.name),
+        ));
+    }
+    for u in input.units.iter().flatten() {
+        items.push(export_item(
+            "units",
+            &u.name,
+            Some(u.quantity),
+            None,
+            Some(u.exclude_from_bom),
+            u.exclude_from_overview,
+            inventory.units.get(&u.name),
+        ));
+    }
+    for t in input.tools.iter().flatten() {
+        items.push(export_item(
+            "tools",
+            &t.name,
+            None,
+            t.setting.as_ref(),
+            Some(t.exclude_from_bom),
+            t.exclude_from_overview,
+            inventory.tools.get(&t.name),
+        ));
+    }
+    for c in input.consumables.iter().flatten() {
+        items.push(export_item(
+            "consumables",
+            &c.name,
+            None,
+            None,
+            Some(c.exclude_from_bom),
+            c.exclude_from_overview,
+            inventory.consumables.get(&c.name),
+        ));
+    }
+
+    items
+}
+
+fn export_output_items(output: &OutputMetadata, inventory: &Inventory) -> Vec<ItemExport> {
+    let mut items = Vec::new();
+
+    for p in output.custom_parts.iter().flatten() {
+        items.push(export_item(
+            "custom_parts",
+            &p.name,
+            Some(p.quantity),
+            None,
+            None,
+            p.exclude_from_overview,
+            inventory.custom_parts.get(&p.name),
+        ));
+    }
+    for s in output.subassemblies.iter().flatten() {
+        items.push(export_item(
+            "subassemblies",
+            &s.name,
+            Some(s.quantity),
+            None,
+            None,
+            s.exclude_from_overview,
+            inventory.subassemblies.get(&s.name),
+        ));
+    }
+    for a in output.assemblies.iter().flatten() {
+        items.push(export_item(
+            "assemblies",
+            &a.name,
+            Some(a.quantity),
+            None,
+            None,
+            a.exclude_from_overview,
+            inventory.assemblies.get(&a.name),
+        ));
+    }
+    for u in output.units.iter().flatten() {
+        items.push(export_item(
+            "units",
+            &u.name,
+            Some(u.quantity),
+            None,
+            None,
+            u.exclude_from_overview,
+            inventory.units.get(&u.name),
+        ));
+    }
+
+    items
+}
+
+fn export_chapter(
+    name: &str,
+    path: Option<&std::path::Path>,
+    metadata: &ChapterMetadata,
+    inventory: &Inventory,
+    missing: &mut Vec<MissingItem>,
+) -> ChapterExport {
+    // Steps are exported in document order
+    let steps = metadata
+        .sections
+        .iter()
+        .map(|(key, section_metadata)| {
+            let inputs = section_metadata
+                .input
+                .as_ref()
+                .map(|input| export_input_items(input, inventory))
+                .unwrap_or_default();
+            let outputs = section_metadata
+                .output
+                .as_ref()
+                .map(|output| export_output_items(output, inventory))
+                .unwrap_or_default();
+
+            // Anything that did not resolve against the inventory is reported as missing
+            for (role, items) in [("input", &inputs), ("output", &outputs)] {
+                for item in items.iter().filter(|i| i.inventory.is_none()) {
+                    missing.push(MissingItem {
+                        category: item.category,
+                        name: item.name.clone(),
+                        role,
+                        chapter: name.to_string(),
+                        step: key.clone(),
+                    });
+                }
+            }
+
+            StepExport {
+                key: key.clone(),
+                inputs,
+                outputs,
+            }
+        })
+        .collect();
+
+    ChapterExport {
+        name: name.to_string(),
+        path: path.map(|p| p.to_string_lossy().to_string()),
+        exclude_from_bom: metadata.exclude_from_bom,
+        steps,
+    }
+}
+
+fn sorted_by_name<T>(items: &HashMap<String, T>) -> Vec<&T> {
+    let mut sorted: Vec<_> = items.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    sorted.into_iter().map(|(_, item)| item).collect()
+}
+
+fn generate_bom_json_file(
+    chapters: &[ChapterExport],
+    totals: &BomTotals,
+    build_quantity: u32,
+    shortages: &[Shortage],
+    missing: &[MissingItem],
+    output_path: &str,
+) -> Result<(), Error> {
+    let export = BomExport {
+        schema_version: BOM_JSON_SCHEMA_VERSION,
+        generator: format!("mdbook-bom {}", env!("CARGO_PKG_VERSION")),
+        chapters,
+        totals: BomTotalsExport {
+            hardware: sorted_by_name(&totals.fasteners),
+            electronics: sorted_by_name(&totals.electronics),
+            custom_parts: sorted_by_name(&totals.custom_parts),
+            consumables: sorted_by_name(&totals.consumables),
+            tools: sorted_by_name(&totals.tools),
+            assemblies: sorted_by_name(&totals.assemblies),
+            subassemblies: sorted_by_name(&totals.subassemblies),
+            units: sorted_by_name(&totals.units),
+        },
+        build_quantity,
+        shortages,
+        missing,
+    };
+
+    let file = std::fs::File::create(output_path).map_err(|e| {
+        Error::msg(format!(
+            "Failed to create JSON file '{}': {}",
+            output_path, e
+        ))
+    })?;
+    serde_json::to_writer_pretty(io::BufWriter::new(file), &export)
+        .map_err(|e| Error::msg(format!("Failed to write JSON file: {}", e)))?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize)]
+struct Diagnostic {
+    severity: Severity,
+    code: &'static str,
+    file: String,
+    line: Option<usize>,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckReport<'a> {
+    errors: usize,
+    warnings: usize,
+    diagnostics: &'a [Diagnostic],
+}
+
+fn category_label(category: &str) -> &'static str {
+    match category {
+        "hardware" => "Hardware",
+        "electronics" => "Electronic component",
+        "custom_parts" => "Custom part",
+        "consumables" => "Consumable",
+        "tools" => "Tool",
+        "assemblies" => "Assembly",
+        "subassemblies" => "Subassembly",
+        "units" => "Unit",
+        _ => "Item",
+    }
+}
+
+// `{{#bom-part}}` references to names missing from the inventory, outside of code;
+// `line_offset` is the file line of the first line of `body`
+fn check_part_references(
+    body: &str,
+    line_offset: usize,
+    file: &str,
+    inventory: &Inventory,
+) -> Vec<Diagnostic> {
+    let re = part_reference_re();
+    let code = code_ranges(body);
+
+    re.captures_iter(body)
+        .filter(|caps| caps[1].is_empty() && inventory.categories_of(&caps[2]).is_empty())
+        .filter(|caps| !in_code(&code, caps.get(0).unwrap().start()))
+        .map(|caps| {
+            let line_idx = body[..caps.get(0).unwrap().start()].matches('\n').count();
+            Diagnostic {
+                severity: Severity::Error,
+                code: "missing-part",
+                file: file.to_string(),
+                line: Some(line_idx + line_offset),
+                message: format!("Part reference '{}' not found in inventory", &caps[2]),
+            }
+        })
+        .collect()
+}
+
+fn check_inline_declarations(
+    declarations: &[InlineDeclaration],
+    line_offset: usize,
+    file: &str,
+    inventory: &Inventory,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for declaration in declarations {
+        let mut diagnostic = |severity, code, message| {
+            diagnostics.push(Diagnostic {
+                severity,
+                code,
+                file: file.to_string(),
+                line: Some(declaration.line_idx + line_offset),
+                message,
+            })
+        };
+
+        let item = match &declaration.item {
+            Ok(item) => item,
+            Err(e) => {
+                diagnostic(Severity::Error, "invalid-declaration", e.clone());
+                continue;
+            }
+        };
+        if declaration.step_key.is_none() {
+            diagnostic(
+                Severity::Error,
+                "orphan-declaration",
+                format!(
+                    "'{}' is declared outside of a step; it is left out of the tables and BOM",
+                    item.name
+                ),
+            );
+        }
+        if inventory.contains(item.category, &item.name) {
+            continue;
+        }
+        let other_categories = inventory.categories_of(&item.name);
+        if other_categories.is_empty() {
+            diagnostic(
+                Severity::Error,
+                "missing-part",
+                format!(
+                    "{} '{}' not found in inventory",
+                    category_label(item.category),
+                    item.name
+                ),
+            );
+        } else {
+            diagnostic(
+                Severity::Error,
+                "category-mismatch",
+                format!(
+                    "'{}' is declared as {} but the inventory has it under {}",
+                    item.name,
+                    item.category,
+                    other_categories.join(", ")
+                ),
+            );
+        }
+    }
+    diagnostics
+}
+
+// The YAML parser's error for a repeated key
+static DUPLICATE_KEY_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+// Validates a chapter's front matter against the inventory and its step headers.
+// Line numbers refer to the chapter source file including the front matter.
+fn check_chapter(
+    content: &str,
+    file: &str,
+    inventory: &Inventory,
+    matcher: &StepMatcher,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let diagnostic = |severity, code, line, message| Diagnostic {
+        severity,
+        code,
+        file: file.to_string(),
+        line,
+        message,
+    };
+
+    let front_matter = match extract_front_matter(content) {
+        Some(fm) => fm,
+        None => {
+            let declarations = find_inline_declarations(content, &IndexMap::new(), matcher);
+            let mut diagnostics = check_part_references(content, 1, file, inventory);
+            diagnostics.extend(check_inline_declarations(&declarations, 1, file, inventory));
+            diagnostics.sort_by_key(|d| d.line);
+            return diagnostics;
+        }
+    };
+    let fm_lines: Vec<&str> = front_matter.lines().collect();
+    // The opening `---` is line 1, so front matter line N is file line N + 1.
+    // The body starts on the closing `---` line.
+    let content_line_offset = fm_lines.len() + 2;
+
+    // Typed parsing silently keeps the last of duplicate keys, a generic value does not
+    if let Err(e) = serde_yml::from_str::<serde_yml::Value>(&front_matter) {
+        let message = e.to_string();
+        let duplicate_re = DUPLICATE_KEY_RE
+            .get_or_init(|| regex::Regex::new(r#"duplicate entry with key "([^"]*)""#).unwrap());
+        if let Some(caps) = duplicate_re.captures(&message) {
+            let key = &caps[1];
+            // Report the second occurrence, which is the one that wins
+            let line = fm_lines
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| l.trim().trim_start_matches("- ") == format!("{}:", key))
+                .nth(1)
+                .map(|(idx, _)| idx + 2)
+                .or_else(|| e.location().map(|l| l.line() + 1));
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                "duplicate-key",
+                line,
+                format!(
+                    "Duplicate key '{}' in front matter; only the last one is used",
+                    key
+                ),
+            ));
+        }
+    }
+
+    let metadata = match serde_yml::from_str::<ChapterMetadata>(&front_matter) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                "invalid-front-matter",
+                e.location().map(|l| l.line() + 1),
+                format!("Front matter could not be parsed: {}", e),
+            ));
+            return diagnostics;
+        }
+    };
+
+    let body = remove_front_matter(content);
+    let step_headers = find_step_headers(&body, &metadata.sections, matcher);
+    diagnostics.extend(check_part_references(
+        &body,
+        content_line_offset,
+        file,
+        inventory,
+    ));
+
+    // Inline declarations are checked where they are written; the front matter checks
+    // below only need to know which steps they add sections for
+    let declarations = find_inline_declarations(&body, &metadata.sections, matcher);
+    diagnostics.extend(check_inline_declarations(
+        &declarations,
+        content_line_offset,
+        file,
+        inventory,
+    ));
+    let declared_steps: std::collections::HashSet<&String> = declarations
+        .iter()
+        .filter_map(|d| d.step_key.as_ref())
+        .collect();
+
+    let mut seen_headers = std::collections::HashSet::new();
+    for header in &step_headers {
+        let (step_key, line_idx) = (&header.key, header.line_idx);
+        if !seen_headers.insert(step_key) {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                "duplicate-header",
+                Some(line_idx + content_line_offset),
+                format!(
+                    "Step header for '{}' appears more than once; tables are inserted after each",
+                    step_key
+                ),
+            ));
+        }
+        let has_section =
+            |key: &String| metadata.sections.contains_key(key) || declared_steps.contains(key);
+        if has_section(step_key) {
+            continue;
+        }
+        if let Some(unqualified) = header
+            .unqualified_key
+            .as_ref()
+            .filter(|key| has_section(key))
+        {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                "unqualified-sub-step",
+                Some(line_idx + content_line_offset),
+                format!(
+                    "Sub-step header belongs to section '{}', which does not exist; '{}' is only used by a top-level step",
+                    step_key, unqualified
+                ),
+            ));
+        } else {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                "unmatched-header",
+                Some(line_idx + content_line_offset),
+                format!("Step header has no front matter section '{}'", step_key),
+            ));
+        }
+    }
+
+    let mut section_keys: Vec<&String> = metadata.sections.keys().collect();
+    section_keys.sort();
+
+    for key in section_keys {
+        let section_metadata = &metadata.sections[key];
+        let key_idx = fm_lines
+            .iter()
+            .position(|l| l.trim() == format!("{}:", key))
+            .unwrap_or(0);
+        // Best effort: the nth mention of the name after its section key
+        let item_line = |name: &str, nth: usize| {
+            fm_lines
+                .iter()
+                .enumerate()
+                .skip(key_idx)
+                .filter(|(_, l)| l.contains(name))
+                .nth(nth)
+                .map(|(idx, _)| idx + 2)
+        };
+
+        if !step_headers.iter().any(|header| &header.key == key) {
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                "unmatched-section",
+                Some(key_idx + 2),
+                format!(
+                    "Section '{}' has no matching step header; its tables will not be rendered",
+                    key
+                ),
+            ));
+        }
+
+        let inputs = section_metadata
+            .input
+            .as_ref()
+            .map(|input| export_input_items(input, inventory))
+            .unwrap_or_default();
+        let outputs = section_metadata
+            .output
+            .as_ref()
+            .map(|output| export_output_items(output, inventory))
+            .unwrap_or_default();
+
+        for (role, items) in [("input", &inputs), ("output", &outputs)] {
+            let mut seen_items: HashMap<(&str, &String), usize> = HashMap::new();
+            for item in items {
+                let occurrence = seen_items.entry((item.category, &item.name)).or_insert(0);
+                *occurrence += 1;
+                if *occurrence > 1 {
+                    diagnostics.push(diagnostic(
+                        Severity::Warning,
+                        "duplicate-item",
+                        item_line(&item.name, *occurrence - 1),
+                        format!(
+                            "'{}' is listed more than once in {} {} of '{}'",
+                            item.name, role, item.category, key
+                        ),
+                    ));
+                }
+
+                if item.inventory.is_some() {
+                    continue;
+                }
+                let other_categories = inventory.categories_of(&item.name);
+                if other_categories.is_empty() {
+                    diagnostics.push(diagnostic(
+                        Severity::Error,
+                        "missing-part",
+                        item_line(&item.name, 0),
+                        format!(
+                            "{} '{}' not found in inventory",
+                            category_label(item.category),
+                            item.name
+                        ),
+                    ));
+                } else {
+                    diagnostics.push(diagnostic(
+                        Severity::Error,
+                        "category-mismatch",
+                        item_line(&item.name, 0),
+                        format!(
+                            "'{}' is listed under {} but the inventory has it under {}",
+                            item.name,
+                            item.category,
+                            other_categories.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.line);
+    diagnostics
+}
+
+// Escapes a value for a GitHub Actions workflow command
+fn escape_github_annotation(value: &str, is_property: bool) -> String {
+    let escaped = value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    if is_property {
+        escaped.replace(':', "%3A").replace(',', "%2C")
+    } else {
+        escaped
+    }
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic], format: &str) -> Result<(), Error> {
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+
+    match format {
+        "json" => {
+            let report = CheckReport {
+                errors,
+                warnings,
+                diagnostics,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        "github" => {
+            for d in diagnostics {
+                let command = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                let line = d.line.map(|l| format!(",line={}", l)).unwrap_or_default();
+                println!(
+                    "::{} file={}{},title={}::{}",
+                    command,
+                    escape_github_annotation(&d.file, true),
+                    line,
+                    escape_github_annotation(&format!("mdbook-bom {}", d.code), true),
+                    escape_github_annotation(&d.message, false)
+                );
+            }
+        }
+        _ => {
+            for d in diagnostics {
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                let location = match d.line {
+                    Some(line) => format!("{}:{}", d.file, line),
+                    None => d.file.clone(),
+                };
+                println!("{}: {}[{}]: {}", location, severity, d.code, d.message);
+            }
+            println!("{} error(s), {} warning(s)", errors, warnings);
+        }
+    }
+
+    Ok(())
+}
+
+// Output categories that can appear in a section's `output`
+const OUTPUT_CATEGORIES: [&str; 4] = ["custom_parts", "assemblies", "subassemblies", "units"];
+
+// JSON Schema for the chapter front matter. With an inventory, each category's
+// `name` is restricted to the names in the matching inventory sheet.
+fn generate_front_matter_schema(inventory: Option<&Inventory>) -> Result<serde_json::Value, Error> {
+    let mut schema = serde_json::to_value(schemars::schema_for!(ChapterMetadata))?;
+
+    if let Some(inventory) = inventory {
+        let definitions = [
+            ("InputMetadata", &BOM_CATEGORIES[..]),
+            ("OutputMetadata", &OUTPUT_CATEGORIES[..]),
+        ];
+        for (definition, categories) in definitions {
+            for category in categories {
+                let pointer = format!("/$defs/{}/properties/{}/items", definition, category);
+                if let Some(items) = schema.pointer_mut(&pointer) {
+                    let names = inventory.names(category);
+                    *items = serde_json::json!({
+                        "allOf": [
+                            items.clone(),
+                            { "properties": { "name": { "enum": names } } }
+                        ]
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(schema)
+}
+
+// Inventory for the language server, reloaded whenever the file changes on disk
+struct InventoryCache {
+    path: Option<String>,
+    // Modification time at the last load attempt, successful or not
+    modified: Option<std::time::SystemTime>,
+    attempted: bool,
+    inventory: Option<Inventory>,
+}
+
+impl InventoryCache {
+    // Reloads the inventory when the file changed since the last attempt. Returns
+    // the error of a failed load, so each failure is reported once until the file
+    // changes, also when it is missing and has no modification time.
+    fn refresh(&mut self) -> Option<Error> {
+        let path = self.path.as_ref()?;
+        let modified = expand_home_path(path)
+            .ok()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .and_then(|m| m.modified().ok());
+        if self.attempted && modified == self.modified {
+            return None;
+        }
+        self.attempted = true;
+        self.modified = modified;
+        match Inventory::load(path) {
+            Ok(inventory) => {
+                self.inventory = Some(inventory);
+                None
+            }
+            Err(e) => {
+                self.inventory = None;
+                Some(e)
+            }
+        }
+    }
+}
+
+struct LanguageServer<'a> {
+    connection: &'a Connection,
+    inventory: InventoryCache,
+    documents: HashMap<lsp_types::Url, String>,
+    step_matcher: StepMatcher,
+}
+
+// A `name:` key with the partly typed value before the cursor
+static NAME_VALUE_RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+
+impl LanguageServer<'_> {
+    fn show_message(&self, typ: lsp_types::MessageType, message: String) -> Result<(), Error> {
+        use lsp_types::notification::{Notification as _, ShowMessage};
+        let params = lsp_types::ShowMessageParams { typ, message };
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                ShowMessage::METHOD.to_string(),
+                params,
+            )))?;
+        Ok(())
+    }
+
+    fn refresh_inventory(&mut self) -> Result<(), Error> {
+        if let Some(e) = self.inventory.refresh() {
+            self.show_message(lsp_types::MessageType::ERROR, format!("mdbook-bom: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, req: Request) -> Result<Response, Error> {
+        use lsp_types::request::{Completion, HoverRequest, Request as _};
+
+        let response = match req.method.as_str() {
+            Completion::METHOD => {
+                match serde_json::from_value::<lsp_types::CompletionParams>(req.params) {
+                    Ok(params) => {
+                        self.refresh_inventory()?;
+                        Response::new_ok(req.id, self.completion(&params.text_document_position))
+                    }
+                    Err(e) => {
+                        Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string())
+                    }
+                }
+            }
+            HoverRequest::METHOD => {
+                match serde_json::from_value::<lsp_types::HoverParams>(req.params) {
+                    Ok(params) => {
+                        self.refresh_inventory()?;
+                        Response::new_ok(req.id, self.hover(&params.text_document_position_params))
+                    }
+                    Err(e) => {
+                        Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string())
+                    }
+                }
+            }
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", req.method),
+            ),
+        };
+        Ok(response)
+    }
+
+    fn handle_notification(&mut self, not: Notification) -> Result<(), Error> {
+        use lsp_types::notification::{
+            DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+            Notification as _,
+        };
+
+        let Notification { method, params } = not;
+        match method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Some(params) =
+                    notification_params::<lsp_types::DidOpenTextDocumentParams>(&method, params)
+                {
+                    let uri = params.text_document.uri;
+                    self.documents
+                        .insert(uri.clone(), params.text_document.text);
+                    self.publish_diagnostics(&uri)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Some(params) =
+                    notification_params::<lsp_types::DidChangeTextDocumentParams>(&method, params)
+                {
+                    let uri = params.text_document.uri;
+                    // Full sync: the last change holds the whole document
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.documents.insert(uri.clone(), change.text);
+                    }
+                    self.publish_diagnostics(&uri)?;
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                if let Some(params) =
+                    notification_params::<lsp_types::DidSaveTextDocumentParams>(&method, params)
+                {
+                    self.publish_diagnostics(&params.text_document.uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Some(params) =
+                    notification_params::<lsp_types::DidCloseTextDocumentParams>(&method, params)
+                {
+                    self.documents.remove(&params.text_document.uri);
+                    self.send_diagnostics(&params.text_document.uri, Vec::new())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn send_diagnostics(
+        &self,
+        uri: &lsp_types::Url,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+    ) -> Result<(), Error> {
+        use lsp_types::notification::{Notification as _, PublishDiagnostics};
+        let params = lsp_types::PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        };
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                params,
+            )))?;
+        Ok(())
+    }
+
+    fn publish_diagnostics(&mut self, uri: &lsp_types::Url) -> Result<(), Error> {
+        if !uri.path().ends_with(".md") {
+            return Ok(());
+        }
+        self.refresh_inventory()?;
+
+        let (content, inventory) = match (self.documents.get(uri), &self.inventory.inventory) {
+            (Some(content), Some(inventory)) => (content, inventory),
+            _ => return Ok(()),
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let diagnostics = check_chapter(content, uri.as_str(), inventory, &self.step_matcher)
+            .into_iter()
+            .map(|d| {
+                let line = d.line.unwrap_or(1).saturating_sub(1);
+                let end = lines.get(line).map(|l| utf16_len(l)).unwrap_or(0);
+                lsp_types::Diagnostic {
+                    range: lsp_types::Range::new(
+                        lsp_types::Position::new(line as u32, 0),
+                        lsp_types::Position::new(line as u32, end),
+                    ),
+                    severity: Some(match d.severity {
+                        Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
+                        Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+                    }),
+                    code: Some(lsp_types::NumberOrString::String(d.code.to_string())),
+                    source: Some("mdbook-bom".to_string()),
+                    message: d.message,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        self.send_diagnostics(uri, diagnostics)
+    }
+
+    fn completion(
+        &self,
+        position: &lsp_types::TextDocumentPositionParams,
+    ) -> Option<lsp_types::CompletionResponse> {
+        let content = self.documents.get(&position.text_document.uri)?;
+        let inventory = self.inventory.inventory.as_ref()?;
+        let lines: Vec<&str> = content.lines().collect();
+        let line_idx = position.position.line as usize;
+        if !in_front_matter(&lines, line_idx) {
+            return None;
+        }
+
+        // Only the value of a `name:` key is completed
+        let line = lines.get(line_idx)?;
+        let before = &line[..utf16_to_byte_offset(line, position.position.character)];
+        let name_re = NAME_VALUE_RE.get_or_init(|| {
+            regex::Regex::new(r#"^\s*(?:-\s+)?name:\s*["']?([A-Za-z0-9_.\-]*)$"#).unwrap()
+        });
+        let prefix = name_re.captures(before)?.get(1)?;
+        let category = front_matter_category(&lines, line_idx)?;
+
+        let range = lsp_types::Range::new(
+            lsp_types::Position::new(position.position.line, utf16_len(&before[..prefix.start()])),
+            position.position,
+        );
+        let items = inventory
+            .names(category)
+            .into_iter()
+            .map(|name| lsp_types::CompletionItem {
+                label: name.clone(),
+                kind: Some(lsp_types::CompletionItemKind::VALUE),
+                detail: inventory.describe(category, name),
+                text_edit: Some(lsp_types::CompletionTextEdit::Edit(
+                    lsp_types::TextEdit::new(range, name.clone()),
+                )),
+                ..Default::default()
+            })
+            .collect();
+
+        Some(lsp_types::CompletionResponse::Array(items))
+    }
+
+    fn hover(&self, position: &lsp_types::TextDocumentPositionParams) -> Option<lsp_types::Hover> {
+        let content = self.documents.get(&position.text_document.uri)?;
+        let inventory = self.inventory.inventory.as_ref()?;
+        let lines: Vec<&str> = content.lines().collect();
+        let line_idx = position.position.line as usize;
+        if !in_front_matter(&lines, line_idx) {
+            return None;
+        }
+
+        let line = lines.get(line_idx)?;
+        let cursor = utf16_to_byte_offset(line, position.position.character);
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || "_.-".contains(c);
+        let start = line[..cursor]
+            .rfind(|c: char| !is_name_char(c))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let end = line[cursor..]
+            .find(|c: char| !is_name_char(c))
+            .map(|i| cursor + i)
+            .unwrap_or(line.len());
+        let name = &line[start..end];
+        if name.is_empty() {
+            return None;
+        }
+
+        // Prefer the category of the list the name is in
+        let categories = inventory.categories_of(name);
+        let category = front_matter_category(&lines, line_idx)
+            .filter(|c| categories.contains(c))
+            .or_else(|| categories.first().copied())?;
+
+        let mut value = format!("**{}** ({})", name, category_label(category));
+        if let Some(description) = inventory.describe(category, name) {
+            value.push_str("\n\n");
+            value.push_str(&description);
+        }
+        if let Some(on_hand) = inventory.on_hand(category, name) {
+            value.push_str(&format!("\n\nOn hand: {}", on_hand));
+        }
+
+        Some(lsp_types::Hover {
+            contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(lsp_types::Range::new(
+                lsp_types::Position::new(position.position.line, utf16_len(&line[..start])),
+                lsp_types::Position::new(position.position.line, utf16_len(&line[..end])),
+            )),
+        })
+    }
+}
+
+// Notifications have no response to carry an error, so malformed ones are
+// logged to stderr, which editors show in the server log, and ignored
+fn notification_params<P: serde::de::DeserializeOwned>(
+    method: &str,
+    params: serde_json::Value,
+) -> Option<P> {
+    match serde_json::from_value(params) {
+        Ok(params) => Some(params),
+        Err(e) => {
+            eprintln!(
+                "mdbook-bom: ignoring malformed {} notification: {}",
+                method, e
+            );
+            None
+        }
+    }
+}
+
+fn in_front_matter(lines: &[&str], line_idx: usize) -> bool {
+    if lines.first() != Some(&"---") {
+        return false;
+    }
+    match lines.iter().skip(1).position(|l| *l == "---") {
+        Some(end) => line_idx > 0 && line_idx <= end,
+        None => false,
+    }
+}
+
+// Category of the list a front matter line belongs to, found from the nearest
+// less indented `key:` line above it
+fn front_matter_category(lines: &[&str], line_idx: usize) -> Option<&'static str> {
+    let indent = |l: &str| l.len() - l.trim_start().len();
+    let mut current = indent(lines.get(line_idx)?);
+
+    for line in lines[..line_idx].iter().rev() {
+        if line.trim().is_empty() || indent(line) >= current {
+            continue;
+        }
+        current = indent(line);
+        let trimmed = line.trim();
+        if let Some(category) = BOM_CATEGORIES.iter().find(|c| trimmed == format!("{}:", c)) {
+            return Some(category);
+        }
+        // Any other mapping key (a step, `input:`, `sections:`) ends the search
+        if trimmed.ends_with(':') && !trimmed.starts_with('-') {
+            return None;
+        }
+    }
+    None
+}
+
+fn utf16_len(s: &str) -> u32 {
+    s.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+// LSP positions count UTF-16 code units by default
+fn utf16_to_byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= character {
+            return idx;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `### Step 2` under `## Step 1` followed by a top-level `## Step 2`, with a
+    // `step_2` section but no `step_1_2`
+    const NESTED_STEP_CHAPTER: &str = "---
+sections:
+  step_1:
+    input:
+      hardware:
+        - name: SCREW
+          quantity: 1
+  step_2:
+    input:
+      hardware:
+        - name: NUT
+          quantity: 2
+---
+## Step 1
+
+### Step 2
+
+## Step 2
+";
+
+    #[test]
+    fn nested_step_header_does_not_take_the_top_level_section() {
+        let metadata: ChapterMetadata =
+            serde_yml::from_str(&extract_front_matter(NESTED_STEP_CHAPTER).unwrap()).unwrap();
+        let body = remove_front_matter(NESTED_STEP_CHAPTER);
+        let headers = find_step_headers(&body, &metadata.sections, &StepMatcher::default());
+
+        let keys: Vec<&str> = headers.iter().map(|header| header.key.as_str()).collect();
+        assert_eq!(keys, ["step_1", "step_1_2", "step_2"]);
+        assert_eq!(headers[1].unqualified_key.as_deref(), Some("step_2"));
+
+        // Step 1 only lists its own items, not those of the top-level Step 2
+        let step_1 = step_section_metadata(&headers, 0, &metadata.sections).unwrap();
+        let hardware: Vec<&str> = step_1
+            .input
+            .as_ref()
+            .and_then(|input| input.hardware.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(hardware, ["SCREW"]);
+    }
+
+    #[test]
+    fn check_reports_nested_step_header_matching_only_the_top_level_section() {
+        let diagnostics = check_chapter(
+            NESTED_STEP_CHAPTER,
+            "chapter.md",
+            &Inventory::default(),
+            &StepMatcher::default(),
+        );
+        let headers: Vec<(&str, Option<usize>)> = diagnostics
+            .iter()
+            .filter(|d| d.code.ends_with("-header") || d.code == "unqualified-sub-step")
+            .map(|d| (d.code, d.line))
+            .collect();
+        assert_eq!(headers, [("unqualified-sub-step", Some(16))]);
+    }
+
+    fn text(setting: &str) -> ToolSetting {
+        ToolSetting::Text(setting.to_string())
+    }
+
+    fn nm(value: f64) -> ToolSetting {
+        ToolSetting::Measured {
+            value,
+            unit: "Nm".to_string(),
+        }
+    }
+
+    #[test]
+    fn torque_unit_spellings() {
+        for unit in [
+            "Nm",
+            "N m",
+            "N·m",
+            "N-m",
+            "NM",
+            "newton meters",
+            "Newton-metre",
+        ] {
+            assert_eq!(torque_unit(unit), Some("Nm"), "{}", unit);
+        }
+        for unit in ["in-lb", "inch pounds", "lbf-in", "in·lbf"] {
+            assert_eq!(torque_unit(unit), Some("in-lb"), "{}", unit);
+        }
+        for unit in ["ft-lb", "ft lbs", "lbf-ft", "foot-pounds"] {
+            assert_eq!(torque_unit(unit), Some("ft-lb"), "{}", unit);
+        }
+        // Nanometres, not newton metres
+        assert_eq!(torque_unit("nm"), None);
+        assert_eq!(torque_unit("rpm"), None);
+    }
+
+    #[test]
+    fn torque_text_normalizes_to_measured() {
+        assert_eq!(text("5Nm").normalized(), nm(5.0));
+        assert_eq!(text(" 5 N·m ").normalized(), nm(5.0));
+        assert_eq!(text("2.5 newton meters").normalized(), nm(2.5));
+        assert_eq!(
+            text("40 lbf-in").normalized(),
+            ToolSetting::Measured {
+                value: 40.0,
+                unit: "in-lb".to_string()
+            }
+        );
+        assert_eq!(
+            ToolSetting::Measured {
+                value: 5.0,
+                unit: "N m".to_string()
+            }
+            .normalized(),
+            nm(5.0)
+        );
+    }
+
+    #[test]
+    fn other_text_is_kept_verbatim() {
+        for setting in ["5 nm", "3000 rpm", "2 turns", "50%", "Speed 3"] {
+            assert_eq!(text(setting).normalized(), text(setting), "{}", setting);
+        }
+        assert_eq!(text("  Speed 3 ").normalized(), text("Speed 3"));
+    }
+
+    #[test]
+    fn display_converts_torques_and_rounds_only_conversions() {
+        let units = ["Nm".to_string(), "in-lb".to_string(), "ft-lb".to_string()];
+        assert_eq!(nm(5.0).display(&units), "5 Nm (44.3 in-lb, 3.7 ft-lb)");
+        assert_eq!(
+            text("3.14159 N·m").display(&units[..2]),
+            "3.14159 Nm (27.8 in-lb)"
+        );
+        assert_eq!(text("40 in-lb").display(&units[..1]), "40 in-lb (4.5 Nm)");
+        assert_eq!(
+            text("10 ft-lb").display(&units[1..2]),
+            "10 ft-lb (120 in-lb)"
+        );
+        // Without configured units, or for other text, nothing is added
+        assert_eq!(nm(5.0).display(&[]), "5 Nm");
+        assert_eq!(text("5 nm").display(&units), "5 nm");
+    }
+
+    #[test]
+    fn format_value_drops_trailing_zeros() {
+        assert_eq!(format_value(44.253, 1), "44.3");
+        assert_eq!(format_value(120.0, 1), "120");
+        assert_eq!(format_value(4.519, 2), "4.52");
+        assert_eq!(format_value(0.04, 1), "0");
+    }
+
+    #[test]
+    fn checklist_version_follows_the_steps() {
+        let metadata = |content: &str| chapter_items(content, &StepMatcher::default()).unwrap().0;
+        let original = metadata(NESTED_STEP_CHAPTER);
+        let reworded = metadata(
+            &NESTED_STEP_CHAPTER.replace("## Step 2\n", "## Step 2\n\nTighten the nut.\n"),
+        );
+        let changed = metadata(&NESTED_STEP_CHAPTER.replace("quantity: 2", "quantity: 3"));
+
+        assert_eq!(steps_hash(&original).len(), 16);
+        assert_eq!(steps_hash(&original), steps_hash(&reworded));
+        assert_ne!(steps_hash(&original), steps_hash(&changed));
+    }
+
+    #[test]
+    fn part_references_in_code_are_left_alone() {
+        let body = "Use {{#bom-part GONE}}.\n\n```markdown\n{{#bom-part SAMPLE}}\n```\n\nWrite `{{#bom-part SPAN}}` inline.\n";
+        let inventory = Inventory::default();
+
+        let lines: Vec<(Option<usize>, &str)> =
+            check_part_references(body, 1, "chapter.md", &inventory)
+                .iter()
+                .map(|d| (d.line, d.code))
+                .collect();
+        assert_eq!(lines, [(Some(1), "missing-part")]);
+
+        let expanded = expand_part_references(
+            body,
+            &IndexMap::new(),
+            &inventory,
+            &StepMatcher::default(),
+            RenderMode::Html,
+        );
+        assert!(expanded.contains("```markdown\n{{#bom-part SAMPLE}}\n```"));
+        assert!(expanded.contains("`{{#bom-part SPAN}}`"));
+        assert!(!expanded.contains("{{#bom-part GONE}}"));
+    }
+
+    #[test]
+    fn part_reference_names_are_escaped() {
+        let expanded = expand_part_references(
+            "Use {{#bom-part <b>&\"x}}.",
+            &IndexMap::new(),
+            &Inventory::default(),
+            &StepMatcher::default(),
+            RenderMode::Html,
+        );
+        assert!(
+            expanded.contains(">&lt;b&gt;&amp;&quot;x</span>"),
+            "{}",
+            expanded
+        );
+    }
+}